@@ -0,0 +1,110 @@
+//! Buffering console messages, so a burst of events is displayed by a single `console.log` when the browser is idle
+
+use std::sync::Arc;
+#[cfg(target_arch = "wasm32")]
+use std::sync::Weak;
+
+use wasm_bindgen::prelude::*;
+
+use super::{log1, log_variadic, SharedState};
+
+/// Console messages waiting to be displayed, see [WASMLayerConfigBuilder::set_batch_console](super::WASMLayerConfigBuilder::set_batch_console)
+#[derive(Default)]
+pub(crate) struct ConsoleBatch {
+    /// The messages, one per line
+    format: String,
+    /// Arguments of the `%c` directives of the messages, in order
+    styles: Vec<String>,
+    /// A flush of the batch is scheduled
+    scheduled: bool,
+}
+
+impl SharedState {
+    /// Add a message to the console batch, scheduling a flush if there is none yet
+    pub(crate) fn push_console_batch(self: &Arc<Self>, message: &str, styles: &[&str]) {
+        let schedule = {
+            let mut batch = self.console_batch.lock().expect("console batch");
+            if !batch.format.is_empty() {
+                batch.format.push('\n');
+            }
+            batch.format.push_str(message);
+            batch
+                .styles
+                .extend(styles.iter().map(|style| style.to_string()));
+            !core::mem::replace(&mut batch.scheduled, true)
+        };
+        if schedule && !schedule_flush(self) {
+            self.flush_console_batch();
+        }
+    }
+
+    /// Display the batched messages with a single `console.log`
+    pub(crate) fn flush_console_batch(&self) {
+        let batch = core::mem::take(&mut *self.console_batch.lock().expect("console batch"));
+        if batch.format.is_empty() {
+            return;
+        }
+        if batch.styles.is_empty() {
+            log1(batch.format);
+        } else {
+            let items = js_sys::Array::of1(&JsValue::from(batch.format));
+            for style in batch.styles {
+                items.push(&JsValue::from(style));
+            }
+            log_variadic(&items);
+        }
+    }
+}
+
+/// Flush the console batch with `requestIdleCallback`, or `queueMicrotask` where it is missing,
+/// returns false if neither exists
+#[cfg(target_arch = "wasm32")]
+fn schedule_flush(shared: &Arc<SharedState>) -> bool {
+    let global = js_sys::global();
+    let scheduler = ["requestIdleCallback", "queueMicrotask"]
+        .iter()
+        .filter_map(|name| js_sys::Reflect::get(&global, &JsValue::from_str(name)).ok())
+        .find(JsValue::is_function);
+    let scheduler = match scheduler {
+        Some(scheduler) => scheduler.unchecked_into::<js_sys::Function>(),
+        None => return false,
+    };
+    // the layer may be dropped before the flush, which has already happened then
+    let shared: Weak<SharedState> = Arc::downgrade(shared);
+    let flush = Closure::once_into_js(move || {
+        if let Some(shared) = shared.upgrade() {
+            shared.flush_console_batch();
+        }
+    });
+    scheduler.call1(&global, &flush).is_ok()
+}
+
+/// There is no event loop outside of wasm, the batch is flushed when the layer is dropped
+#[cfg(not(target_arch = "wasm32"))]
+fn schedule_flush(_shared: &Arc<SharedState>) -> bool {
+    true
+}
+
+/// Flush the console batch synchronously on `beforeunload`, so the last messages of a page are not lost
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn flush_before_unload(shared: &Arc<SharedState>) {
+    let global = js_sys::global();
+    let add_event_listener =
+        match js_sys::Reflect::get(&global, &JsValue::from_str("addEventListener")) {
+            Ok(function) if function.is_function() => function.unchecked_into::<js_sys::Function>(),
+            _ => return,
+        };
+    let shared: Weak<SharedState> = Arc::downgrade(shared);
+    let flush = Closure::<dyn FnMut()>::new(move || {
+        if let Some(shared) = shared.upgrade() {
+            shared.flush_console_batch();
+        }
+    });
+    let _ = add_event_listener.call2(&global, &JsValue::from_str("beforeunload"), flush.as_ref());
+    // the listener lives as long as the page
+    flush.forget();
+}
+
+/// There is no page to unload outside of wasm
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn flush_before_unload(_shared: &Arc<SharedState>) {}
@@ -0,0 +1,257 @@
+//! A layer capturing formatted events in memory, to test what instrumented code logs without a browser
+
+use std::sync::{Arc, Mutex};
+
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::{
+    format_recorded_event, record_new_span, record_span_values, remove_span_data, LayerId,
+    SpanContext, StringRecorder, WASMLayerConfig,
+};
+
+/// An event captured by a [CapturingLayer]
+#[derive(Debug, PartialEq, Clone)]
+pub struct CapturedEvent {
+    pub level: tracing::Level,
+    pub target: String,
+    /// The console line [WASMLayer](super::WASMLayer) would report, without color styling
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+}
+
+/// Implements [tracing_subscriber::layer::Layer] by storing formatted events in memory instead of reporting them to the browser
+///
+/// Clones of a layer share the same captured events, so keep one around to [CapturingLayer::drain] them.
+#[derive(Clone)]
+pub struct CapturingLayer {
+    config: Arc<WASMLayerConfig>,
+    /// Key of the data this layer stores in the extensions of spans, shared by its clones
+    id: LayerId,
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+}
+
+impl CapturingLayer {
+    pub fn new(config: WASMLayerConfig) -> Self {
+        CapturingLayer {
+            config: Arc::new(config),
+            id: LayerId::next(),
+            events: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Take all the events captured so far
+    pub fn drain(&self) -> Vec<CapturedEvent> {
+        self.events
+            .lock()
+            .expect("captured events")
+            .drain(..)
+            .collect()
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for CapturingLayer {
+    /// The same levels as the console output of a [WASMLayer](super::WASMLayer) with this config
+    fn enabled(&self, metadata: &tracing::Metadata<'_>, _: Context<'_, S>) -> bool {
+        self.config.level_enabled(
+            metadata,
+            self.config.max_level,
+            self.config.console_max_level,
+        )
+    }
+
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::Id,
+        ctx: Context<'_, S>,
+    ) {
+        if let Some(span_ref) = ctx.span(id) {
+            record_new_span(
+                &self.config,
+                self.id,
+                self.config.show_span_fields,
+                attrs,
+                &span_ref,
+            );
+        }
+    }
+
+    fn on_record(&self, id: &tracing::Id, values: &tracing::span::Record<'_>, ctx: Context<'_, S>) {
+        if !self.config.show_span_fields {
+            return;
+        }
+        if let Some(span_ref) = ctx.span(id) {
+            record_span_values(self.id, values, &span_ref);
+        }
+    }
+
+    fn on_close(&self, id: tracing::Id, ctx: Context<'_, S>) {
+        if let Some(span_ref) = ctx.span(&id) {
+            remove_span_data(self.id, &span_ref);
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        let mut recorder = StringRecorder::new(&self.config);
+        event.record(&mut recorder);
+        let meta = event.metadata();
+        let span = SpanContext::new(&self.config, self.id, ctx.event_span(event));
+
+        let captured = CapturedEvent {
+            level: *meta.level(),
+            target: meta.target().to_string(),
+            message: format_recorded_event(&self.config, meta, &span, &recorder).plain(),
+            fields: recorder
+                .fields
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        };
+        self.events.lock().expect("captured events").push(captured);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{thread_display_suffix, WASMLayerConfigBuilder};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_capture_events() {
+        let layer = CapturingLayer::new(
+            WASMLayerConfigBuilder::new()
+                .set_show_origin(false)
+                .set_show_span_context(true)
+                .set_max_level(tracing::Level::INFO)
+                .build(),
+        );
+        let subscriber = tracing_subscriber::Registry::default().with(layer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request").in_scope(|| {
+                tracing::info!(target: "app", id = 7, "handled");
+            });
+            tracing::debug!("filtered out");
+        });
+
+        assert_eq!(
+            layer.drain(),
+            vec![CapturedEvent {
+                level: tracing::Level::INFO,
+                target: "app".to_string(),
+                message: format!("INFO{}  request: handled id = 7;", thread_display_suffix()),
+                fields: vec![("id".to_string(), "7".to_string())],
+            }]
+        );
+        assert!(layer.drain().is_empty());
+    }
+
+    #[test]
+    fn test_capture_span_fields() {
+        let layer = CapturingLayer::new(
+            WASMLayerConfigBuilder::new()
+                .set_show_origin(false)
+                .set_show_span_fields(true)
+                .build(),
+        );
+        let subscriber = tracing_subscriber::Registry::default().with(layer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", id = 7, user = tracing::field::Empty);
+            span.in_scope(|| tracing::info!("started"));
+            span.record("user", "alice");
+            span.in_scope(|| tracing::info!("authenticated"));
+        });
+
+        let messages: Vec<String> = layer
+            .drain()
+            .into_iter()
+            .map(|event| event.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                format!("INFO{}  started {{id = 7;}}", thread_display_suffix()),
+                format!(
+                    "INFO{}  authenticated {{id = 7; user = \"alice\";}}",
+                    thread_display_suffix()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_capture_console_levels() {
+        let layer = CapturingLayer::new(
+            WASMLayerConfigBuilder::new()
+                .set_show_origin(false)
+                .set_console_max_level(tracing::Level::INFO)
+                .build(),
+        );
+        let subscriber = tracing_subscriber::Registry::default().with(layer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("console only shows info");
+            tracing::warn!("shown");
+        });
+
+        let levels: Vec<tracing::Level> =
+            layer.drain().into_iter().map(|event| event.level).collect();
+        assert_eq!(levels, vec![tracing::Level::WARN]);
+
+        let layer = CapturingLayer::new(
+            WASMLayerConfigBuilder::new()
+                .set_enabled_levels([tracing::Level::ERROR, tracing::Level::DEBUG])
+                .build(),
+        );
+        let subscriber = tracing_subscriber::Registry::default().with(layer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("enabled");
+            tracing::warn!("not enabled");
+        });
+
+        let levels: Vec<tracing::Level> =
+            layer.drain().into_iter().map(|event| event.level).collect();
+        assert_eq!(levels, vec![tracing::Level::DEBUG]);
+    }
+
+    #[test]
+    fn test_capture_next_to_wasm_layer() {
+        let layer = CapturingLayer::new(
+            WASMLayerConfigBuilder::new()
+                .set_show_origin(false)
+                .set_show_span_fields(true)
+                .build(),
+        );
+        let quiet = WASMLayerConfigBuilder::new()
+            .set_console_config(crate::ConsoleConfig::NoReporting)
+            .set_report_events_in_timings(false)
+            .set_report_spans_in_timings(false)
+            .set_show_span_relative_time(true)
+            .build();
+        let subscriber = tracing_subscriber::Registry::default()
+            .with(crate::WASMLayer::new(quiet))
+            .with(layer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", id = 7).in_scope(|| tracing::info!("started"));
+        });
+
+        let messages: Vec<String> = layer
+            .drain()
+            .into_iter()
+            .map(|event| event.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![format!(
+                "INFO{}  started {{id = 7;}}",
+                thread_display_suffix()
+            )]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Appending events to an element of the page, for displays without devtools such as kiosks
+
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::host;
+use super::{format_recorded_event, SpanContext, StringRecorder, WASMLayer};
+
+/// Element which events are appended to, see [WASMLayerConfigBuilder::set_dom_sink](super::WASMLayerConfigBuilder::set_dom_sink)
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct DomSink {
+    pub(crate) element_id: String,
+    /// Takes precedence over the id, see [WASMLayerConfigBuilder::set_dom_sink_element](super::WASMLayerConfigBuilder::set_dom_sink_element)
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) element: Option<DomElement>,
+    /// The oldest nodes are removed beyond this number
+    pub(crate) max_nodes: usize,
+}
+
+thread_local! {
+    static DOM_ELEMENTS: RefCell<Vec<(usize, web_sys::Element)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// An element which events are appended to
+///
+/// `web_sys::Element` can't be shared across threads, so the element is kept in a registry of the thread which
+/// set it, under an id unique to the process. Nothing is appended by events reported on any other thread.
+/// The element is unregistered when the last clone is dropped on that thread.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct DomElement(Arc<DomElementId>);
+
+#[derive(Debug, PartialEq)]
+struct DomElementId(usize);
+
+impl DomElement {
+    pub(crate) fn new(element: web_sys::Element) -> Self {
+        static NEXT_DOM_ELEMENT_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT_DOM_ELEMENT_ID.fetch_add(1, Ordering::Relaxed);
+        DOM_ELEMENTS.with(|elements| elements.borrow_mut().push((id, element)));
+        DomElement(Arc::new(DomElementId(id)))
+    }
+
+    /// The element, if it was set on this thread
+    #[cfg(target_arch = "wasm32")]
+    fn get(&self) -> Option<web_sys::Element> {
+        let id = (self.0).0;
+        DOM_ELEMENTS.with(|elements| {
+            elements
+                .borrow()
+                .iter()
+                .find(|(element_id, _)| *element_id == id)
+                .map(|(_, element)| element.clone())
+        })
+    }
+}
+
+impl Drop for DomElementId {
+    fn drop(&mut self) {
+        // the registry may already be destroyed when the thread exits
+        let _ = DOM_ELEMENTS.try_with(|elements| {
+            elements
+                .borrow_mut()
+                .retain(|(element_id, _)| *element_id != self.0)
+        });
+    }
+}
+
+impl WASMLayer {
+    /// Append an event to the element of the DOM sink of the config, if there is one
+    pub(crate) fn append_event_to_dom<S: Subscriber + for<'a> LookupSpan<'a>>(
+        &self,
+        event: &tracing::Event<'_>,
+        recorder: &StringRecorder,
+        ctx: &Context<'_, S>,
+    ) {
+        let dom_sink = match &self.config.dom_sink {
+            Some(dom_sink) => dom_sink,
+            None => return,
+        };
+        let meta = event.metadata();
+        let span = SpanContext::new(&self.config, self.id, ctx.event_span(event));
+        append_node(
+            dom_sink,
+            &format_recorded_event(&self.config, meta, &span, recorder).plain(),
+            self.config.console_colors.for_level(meta.level()),
+        );
+    }
+}
+
+/// Append a `<div>` with `message` to the element of the sink, nothing happens if there is no such element,
+/// e.g. in a worker
+#[cfg(target_arch = "wasm32")]
+fn append_node(dom_sink: &DomSink, message: &str, style: &str) {
+    let element = match &dom_sink.element {
+        Some(element) => element.get(),
+        None => web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.get_element_by_id(&dom_sink.element_id)),
+    };
+    let (element, document) = match element {
+        Some(element) => match element.owner_document() {
+            Some(document) => (element, document),
+            None => return,
+        },
+        None => return,
+    };
+    let node = match document.create_element("div") {
+        Ok(node) => node,
+        Err(_) => return,
+    };
+    node.set_text_content(Some(message));
+    let _ = node.set_attribute("style", &format!("{}; white-space: pre-wrap", style));
+    if element.append_child(&node).is_err() {
+        return;
+    }
+    while element.child_element_count() as usize > dom_sink.max_nodes {
+        match element.first_element_child() {
+            Some(oldest) => oldest.remove(),
+            None => break,
+        }
+    }
+}
+
+/// The element is looked up by its id by the host stand-in
+#[cfg(not(target_arch = "wasm32"))]
+fn append_node(dom_sink: &DomSink, message: &str, style: &str) {
+    host::append_node(&dom_sink.element_id, message, style, dom_sink.max_nodes);
+}
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod test {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    use crate::{host, thread_display_suffix, ConsoleConfig, WASMLayer, WASMLayerConfigBuilder};
+
+    #[test]
+    fn test_dom_sink() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_show_origin(false)
+            .set_dom_sink("log", 50)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("kiosk ready");
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                format!(
+                    r#"console.info("INFO{}  kiosk ready")"#,
+                    thread_display_suffix()
+                ),
+                format!(
+                    r#"dom.append("log", "INFO{}  kiosk ready", "color: whitesmoke; background: #444", 50)"#,
+                    thread_display_suffix()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Inert stand-ins for the JS bindings, so the crate builds and runs on native targets
+//!
+//! Nothing is displayed outside of a JS environment. In tests, the calls are recorded instead,
+//! so the output of the layer can be checked on the host.
+
+#[cfg(feature = "timings")]
+use wasm_bindgen::JsValue;
+
+#[cfg(test)]
+thread_local! {
+    static CALLS: core::cell::RefCell<Vec<String>> = const { core::cell::RefCell::new(Vec::new()) };
+}
+
+/// Take the calls made on this thread, formatted like `console.log("message")`
+#[cfg(all(test, any(feature = "console", feature = "timings")))]
+pub(crate) fn take_calls() -> Vec<String> {
+    CALLS.with(|calls| calls.take())
+}
+
+/// Argument of a binding, as it is shown in the recorded calls
+#[cfg(all(test, any(feature = "console", feature = "timings")))]
+trait HostArg {
+    fn describe(&self) -> String;
+}
+
+#[cfg(all(test, any(feature = "console", feature = "timings")))]
+impl HostArg for &str {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[cfg(all(test, any(feature = "console", feature = "timings")))]
+impl HostArg for String {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[cfg(all(test, any(feature = "console", feature = "timings")))]
+impl HostArg for bool {
+    fn describe(&self) -> String {
+        self.to_string()
+    }
+}
+
+#[cfg(all(test, any(feature = "dom-sink", feature = "indexeddb-sink")))]
+impl HostArg for usize {
+    fn describe(&self) -> String {
+        self.to_string()
+    }
+}
+
+#[cfg(all(test, any(feature = "console", feature = "timings")))]
+impl HostArg for &js_sys::Array {
+    fn describe(&self) -> String {
+        "[..]".to_string()
+    }
+}
+
+#[cfg(all(test, any(feature = "console", feature = "timings")))]
+impl HostArg for &super::StringRecorder {
+    fn describe(&self) -> String {
+        let fields: Vec<_> = self
+            .fields
+            .iter()
+            .map(|(name, value)| format!("{}: {:?}", name, value))
+            .collect();
+        format!("{{{}}}", fields.join(", "))
+    }
+}
+
+#[cfg(all(test, feature = "timings"))]
+impl HostArg for &super::timings::PerformanceOptions<'_> {
+    fn describe(&self) -> String {
+        let mut options = Vec::new();
+        if let Some(start) = self.start {
+            options.push(format!("start: {:?}", start));
+        }
+        if let Some(end) = self.end {
+            options.push(format!("end: {:?}", end));
+        }
+        if let Some((detail, recorder)) = &self.detail {
+            options.push(format!(
+                "detail: {{level: {:?}, target: {:?}, message: {:?}, fields: {}}}",
+                detail.level,
+                detail.target,
+                detail.message.unwrap_or("null"),
+                recorder.describe()
+            ));
+        }
+        format!("{{{}}}", options.join(", "))
+    }
+}
+
+macro_rules! inert_bindings {
+    ($($js_name:literal fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty = $value:expr)?;)*) => {
+        $(
+            #[cfg_attr(not(test), allow(unused_variables))]
+            pub(crate) fn $name($($arg: $ty),*) $(-> $ret)? {
+                #[cfg(test)]
+                {
+                    let args: &[String] = &[$(HostArg::describe(&$arg)),*];
+                    let call = format!("{}({})", $js_name, args.join(", "));
+                    CALLS.with(|calls| calls.borrow_mut().push(call));
+                }
+                $($value)?
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "timings")]
+inert_bindings! {
+    "performance.mark" fn mark(name: &str);
+    "performance.mark" fn mark_with_options(name: &str, options: &super::timings::PerformanceOptions<'_>);
+    "performance.measure" fn measure(name: String, start_mark: String) -> Result<(), JsValue> = Ok(());
+    "performance.measure" fn measure2(name: String, start_mark: &str, end_mark: &str) -> Result<(), JsValue> = Ok(());
+    "performance.measure" fn measure_from_origin(name: String) -> Result<(), JsValue> = Ok(());
+    "performance.measure" fn measure_with_options(name: String, options: &super::timings::PerformanceOptions<'_>) -> Result<(), JsValue> = Ok(());
+}
+
+inert_bindings! {
+    "performance.now" fn performance_now() -> f64 = 0.0;
+    "Date.now" fn date_now() -> f64 = 0.0;
+}
+
+#[cfg(feature = "console")]
+inert_bindings! {
+    "console.log" fn log1(message: String);
+    "console.log" fn log3(message1: &str, message2: &str, message3: &str);
+    "console.log" fn log4(message1: String, message2: &str, message3: &str, message4: &str);
+    "console.log" fn log_variadic(items: &js_sys::Array);
+    "console.debug" fn debug_variadic(items: &js_sys::Array);
+    "console.info" fn info_variadic(items: &js_sys::Array);
+    "console.warn" fn warn_variadic(items: &js_sys::Array);
+    "console.error" fn error_variadic(items: &js_sys::Array);
+    "console.trace" fn trace_variadic(items: &js_sys::Array);
+    "console.table" fn table_fields(recorder: &super::StringRecorder);
+    "console.count" fn count(label: &str);
+    "console.group" fn group(label: &str);
+    "console.groupCollapsed" fn group_collapsed(label: &str);
+    "console.groupEnd" fn group_end();
+    "console.debug" fn debug1(message: String);
+    "console.debug" fn debug3(message1: &str, message2: &str, message3: &str);
+    "console.debug" fn debug4(message1: String, message2: &str, message3: &str, message4: &str);
+    "console.info" fn info1(message: String);
+    "console.info" fn info3(message1: &str, message2: &str, message3: &str);
+    "console.info" fn info4(message1: String, message2: &str, message3: &str, message4: &str);
+    "console.warn" fn warn1(message: String);
+    "console.warn" fn warn3(message1: &str, message2: &str, message3: &str);
+    "console.warn" fn warn4(message1: String, message2: &str, message3: &str, message4: &str);
+    "console.error" fn error1(message: String);
+    "console.error" fn error3(message1: &str, message2: &str, message3: &str);
+    "console.error" fn error4(message1: String, message2: &str, message3: &str, message4: &str);
+    "console.trace" fn trace1(message: String);
+    "console.trace" fn trace3(message1: &str, message2: &str, message3: &str);
+    "console.trace" fn trace4(message1: String, message2: &str, message3: &str, message4: &str);
+    "console.assert" fn assert1(condition: bool, message: String);
+    "console.assert" fn assert3(condition: bool, message1: &str, message2: &str, message3: &str);
+    "console.assert" fn assert4(condition: bool, message1: String, message2: &str, message3: &str, message4: &str);
+    "console.assert" fn assert_variadic(condition: bool, items: &js_sys::Array);
+}
+
+#[cfg(feature = "dom-sink")]
+inert_bindings! {
+    "dom.append" fn append_node(element_id: &str, message: &str, style: &str, max_nodes: usize);
+}
+
+#[cfg(feature = "indexeddb-sink")]
+inert_bindings! {
+    "indexedDB.add" fn push_entry(database_name: &str, entry: String, max_entries: usize);
+}
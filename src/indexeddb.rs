@@ -0,0 +1,410 @@
+//! Persisting recent events in IndexedDB, so the logs of a PWA can be exported after a crash
+
+#[cfg(target_arch = "wasm32")]
+use core::cell::RefCell;
+#[cfg(target_arch = "wasm32")]
+use std::collections::VecDeque;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use web_sys::{
+    IdbDatabase, IdbFactory, IdbKeyRange, IdbObjectStore, IdbObjectStoreParameters, IdbRequest,
+    IdbTransactionMode,
+};
+
+#[cfg(target_arch = "wasm32")]
+use super::date_now;
+#[cfg(not(target_arch = "wasm32"))]
+use super::host::{date_now, push_entry};
+use super::{iso_8601, json_event, StringRecorder, WASMLayer};
+
+/// Database which events are stored in, see [WASMLayerConfigBuilder::set_indexeddb_sink](super::WASMLayerConfigBuilder::set_indexeddb_sink)
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct IndexedDbSink {
+    pub(crate) database_name: String,
+    /// The oldest entries are deleted beyond this number
+    pub(crate) max_entries: usize,
+}
+
+/// Object store of the events in the database, with auto incremented keys
+#[cfg(target_arch = "wasm32")]
+const STORE: &str = "events";
+
+impl WASMLayer {
+    /// Queue an event to be stored in the database of the IndexedDB sink of the config, if there is one
+    ///
+    /// Entries are the JSON of [ConsoleOutputFormat::Json](super::ConsoleOutputFormat::Json) with a `time` field first.
+    pub(crate) fn store_event_in_indexeddb(
+        &self,
+        meta: &tracing::Metadata<'_>,
+        recorder: &StringRecorder,
+    ) {
+        let sink = match &self.config.indexeddb_sink {
+            Some(sink) => sink,
+            None => return,
+        };
+        let json = json_event(meta, recorder);
+        let entry = format!("{{\"time\":\"{}\",{}", iso_8601(date_now()), &json[1..]);
+        push_entry(&sink.database_name, entry, sink.max_entries);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = queueMicrotask)]
+    fn queue_microtask(callback: &JsValue);
+}
+
+/// Connection to a database of the IndexedDB sink
+#[cfg(target_arch = "wasm32")]
+enum Connection {
+    Opening,
+    Open(IdbDatabase),
+    /// The database can't be opened, e.g. IndexedDB is unavailable in private browsing, events are dropped
+    Failed,
+}
+
+/// A database of the IndexedDB sink, and the entries waiting to be written to it
+#[cfg(target_arch = "wasm32")]
+struct Database {
+    name: String,
+    max_entries: usize,
+    connection: Connection,
+    /// At most `max_entries`, the oldest are dropped first
+    pending: VecDeque<String>,
+    /// A write of the pending entries is scheduled
+    scheduled: bool,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Database {
+    fn push(&mut self, entry: String) {
+        if matches!(self.connection, Connection::Failed) || self.max_entries == 0 {
+            return;
+        }
+        if self.pending.len() >= self.max_entries {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(entry);
+    }
+}
+
+// IndexedDB handles can't be shared across threads, each worker opens its own
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    static DATABASES: RefCell<Vec<Database>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(target_arch = "wasm32")]
+fn with_database<T>(name: &str, f: impl FnOnce(&mut Database) -> T) -> Option<T> {
+    DATABASES.with(|databases| {
+        databases
+            .borrow_mut()
+            .iter_mut()
+            .find(|database| database.name == name)
+            .map(f)
+    })
+}
+
+/// Queue an entry, opening the database on the first one, the pending entries are written
+/// in a single transaction once the current task is done
+#[cfg(target_arch = "wasm32")]
+fn push_entry(database_name: &str, entry: String, max_entries: usize) {
+    if with_database(database_name, |_| ()).is_none() {
+        open_sink_database(database_name, max_entries);
+    }
+    with_database(database_name, |database| database.push(entry));
+    schedule_write(database_name);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn open_sink_database(database_name: &str, max_entries: usize) {
+    DATABASES.with(|databases| {
+        databases.borrow_mut().push(Database {
+            name: database_name.to_string(),
+            max_entries,
+            connection: Connection::Opening,
+            pending: VecDeque::new(),
+            scheduled: false,
+        })
+    });
+    let opened_name = database_name.to_string();
+    let failed_name = database_name.to_string();
+    let opened = open_database(
+        database_name,
+        move |db| {
+            with_database(&opened_name, |database| {
+                database.connection = Connection::Open(db)
+            });
+            schedule_write(&opened_name);
+        },
+        move |_| connection_failed(&failed_name),
+    );
+    if opened.is_err() {
+        connection_failed(database_name);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn connection_failed(database_name: &str) {
+    with_database(database_name, |database| {
+        database.connection = Connection::Failed;
+        database.pending.clear();
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn schedule_write(database_name: &str) {
+    let schedule = with_database(database_name, |database| {
+        matches!(database.connection, Connection::Open(_))
+            && !database.pending.is_empty()
+            && !core::mem::replace(&mut database.scheduled, true)
+    });
+    if schedule == Some(true) {
+        let name = database_name.to_string();
+        queue_microtask(&Closure::once_into_js(move || write_pending(&name)));
+    }
+}
+
+/// Write the pending entries of every database now rather than once the current task is done,
+/// e.g. when the module is about to abort on a panic
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn write_all_pending() {
+    let names: Vec<String> = DATABASES.with(|databases| {
+        databases
+            .borrow()
+            .iter()
+            .map(|database| database.name.clone())
+            .collect()
+    });
+    for name in names {
+        write_pending(&name);
+    }
+}
+
+/// Entries are stored by the host stand-in as soon as they are pushed
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn write_all_pending() {}
+
+/// Write the pending entries, then delete the oldest entries beyond the maximal number
+#[cfg(target_arch = "wasm32")]
+fn write_pending(database_name: &str) {
+    let (db, entries, max_entries) = match with_database(database_name, |database| {
+        database.scheduled = false;
+        let db = match &database.connection {
+            Connection::Open(db) => Some(db.clone()),
+            _ => None,
+        };
+        (
+            db,
+            core::mem::take(&mut database.pending),
+            database.max_entries,
+        )
+    }) {
+        Some((Some(db), entries, max_entries)) if !entries.is_empty() => (db, entries, max_entries),
+        _ => return,
+    };
+    let store = match db
+        .transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)
+        .and_then(|transaction| transaction.object_store(STORE))
+    {
+        Ok(store) => store,
+        Err(_) => return,
+    };
+    for entry in entries {
+        let _ = store.add(&JsValue::from(entry));
+    }
+    let count = match store.count() {
+        Ok(count) => count,
+        Err(_) => return,
+    };
+    let count_result = count.clone();
+    count.set_onsuccess(Some(
+        Closure::once_into_js(move || {
+            let stored = count_result
+                .result()
+                .ok()
+                .and_then(|count| count.as_f64())
+                .unwrap_or(0.0) as usize;
+            if stored > max_entries {
+                delete_oldest(store, stored - max_entries);
+            }
+        })
+        .unchecked_ref(),
+    ));
+}
+
+#[cfg(target_arch = "wasm32")]
+fn delete_oldest(store: IdbObjectStore, excess: usize) {
+    let keys = match store.get_all_keys_with_key_and_limit(&JsValue::UNDEFINED, excess as u32) {
+        Ok(keys) => keys,
+        Err(_) => return,
+    };
+    let keys_result = keys.clone();
+    keys.set_onsuccess(Some(
+        Closure::once_into_js(move || {
+            let keys = match keys_result.result() {
+                Ok(keys) => keys.unchecked_into::<js_sys::Array>(),
+                Err(_) => return,
+            };
+            if keys.length() > 0 {
+                let newest_deleted = keys.get(keys.length() - 1);
+                if let Ok(range) = IdbKeyRange::upper_bound(&newest_deleted) {
+                    let _ = store.delete(&range);
+                }
+            }
+        })
+        .unchecked_ref(),
+    ));
+}
+
+/// Open a database, creating its object store if it is new, and call `on_open` with the `IDBDatabase`,
+/// or `on_error` with the error of the request if it fails later
+#[cfg(target_arch = "wasm32")]
+fn open_database(
+    database_name: &str,
+    on_open: impl FnOnce(IdbDatabase) + 'static,
+    on_error: impl FnOnce(JsValue) + 'static,
+) -> Result<(), JsValue> {
+    let factory = match js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("indexedDB")) {
+        Ok(factory) if factory.is_object() => factory.unchecked_into::<IdbFactory>(),
+        _ => return Err(JsValue::from_str("tracing-wasm: indexedDB is unavailable")),
+    };
+    let request = factory.open_with_u32(database_name, 1)?;
+    let upgrade_result = request.clone();
+    request.set_onupgradeneeded(Some(
+        Closure::once_into_js(move || {
+            if let Ok(db) = upgrade_result.result() {
+                let options = IdbObjectStoreParameters::new();
+                options.set_auto_increment(true);
+                let _ = db
+                    .unchecked_ref::<IdbDatabase>()
+                    .create_object_store_with_optional_parameters(STORE, &options);
+            }
+        })
+        .unchecked_ref(),
+    ));
+    let error_request = request.clone();
+    request.set_onerror(Some(
+        Closure::once_into_js(move || on_error(request_error(&error_request))).unchecked_ref(),
+    ));
+    let success_result = request.clone();
+    request.set_onsuccess(Some(
+        Closure::once_into_js(move || {
+            if let Ok(db) = success_result.result() {
+                on_open(db.unchecked_into());
+            }
+        })
+        .unchecked_ref(),
+    ));
+    Ok(())
+}
+
+/// The `DOMException` of a failed request
+#[cfg(target_arch = "wasm32")]
+fn request_error(request: &IdbRequest) -> JsValue {
+    match request.error() {
+        Ok(Some(error)) => error.into(),
+        _ => JsValue::UNDEFINED,
+    }
+}
+
+/// Read the events stored by [WASMLayerConfigBuilder::set_indexeddb_sink](super::WASMLayerConfigBuilder::set_indexeddb_sink),
+/// the promise resolves to a JSON array of the entries, from the oldest
+///
+/// The promise is rejected if IndexedDB is unavailable or the database can't be read.
+/// Await it with `wasm_bindgen_futures::JsFuture`, or hand it to JS to offer the logs as a download.
+#[cfg(target_arch = "wasm32")]
+pub fn export_indexeddb_logs(database_name: &str) -> js_sys::Promise {
+    let name = database_name.to_string();
+    js_sys::Promise::new(&mut |resolve, reject| {
+        let open_rejected = reject.clone();
+        let rejected = reject.clone();
+        let opened = open_database(
+            &name,
+            move |db| {
+                let request = db
+                    .transaction_with_str(STORE)
+                    .and_then(|transaction| transaction.object_store(STORE))
+                    .and_then(|store| store.get_all());
+                let request = match request {
+                    Ok(request) => request,
+                    Err(error) => {
+                        db.close();
+                        let _ = reject.call1(&JsValue::NULL, &error);
+                        return;
+                    }
+                };
+                let failed_db = db.clone();
+                request.set_onerror(Some(
+                    Closure::once_into_js(move || {
+                        failed_db.close();
+                        let _ = reject.call1(
+                            &JsValue::NULL,
+                            &JsValue::from_str("tracing-wasm: the stored events can't be read"),
+                        );
+                    })
+                    .unchecked_ref(),
+                ));
+                let entries_result = request.clone();
+                request.set_onsuccess(Some(
+                    Closure::once_into_js(move || {
+                        db.close();
+                        let entries = entries_result
+                            .result()
+                            .map(|entries| entries.unchecked_into::<js_sys::Array>())
+                            .unwrap_or_default();
+                        let json = format!("[{}]", String::from(entries.join(",")));
+                        let _ = resolve.call1(&JsValue::NULL, &JsValue::from(json));
+                    })
+                    .unchecked_ref(),
+                ));
+            },
+            move |error| {
+                let _ = open_rejected.call1(&JsValue::NULL, &error);
+            },
+        );
+        if let Err(error) = opened {
+            let _ = rejected.call1(&JsValue::NULL, &error);
+        }
+    })
+}
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod test {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    use crate::{host, ConsoleConfig, WASMLayer, WASMLayerConfigBuilder};
+
+    #[test]
+    fn test_indexeddb_sink() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::NoReporting)
+            .set_report_events_in_timings(false)
+            .set_indexeddb_sink("logs", 500)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "app::sync", retries = 2, "offline");
+        });
+
+        let entries: Vec<_> = host::take_calls()
+            .into_iter()
+            .filter(|call| call.starts_with("indexedDB.add"))
+            .collect();
+        assert_eq!(entries.len(), 1);
+        // `Date.now()` is the epoch outside of a JS environment
+        assert!(entries[0].starts_with(
+            r#"indexedDB.add("logs", "{\"time\":\"1970-01-01T00:00:00.000Z\",\"level\":\"WARN\",\"target\":\"app::sync\","#
+        ));
+        assert!(entries[0]
+            .ends_with(r#"\"message\":\"offline\",\"fields\":{\"retries\":\"2\"}}", 500)"#));
+    }
+}
@@ -0,0 +1,40 @@
+//! Functions exported to JS, to reconfigure the global layer from the browser console or an admin UI
+//!
+//! They are exported by wasm-bindgen with their Rust names, e.g. `wasm.set_log_level_js("debug")`,
+//! and only affect the layer set by the `set_as_global_default*` functions.
+
+use std::sync::Mutex;
+
+use wasm_bindgen::prelude::*;
+
+use super::WASMLayerHandle;
+
+/// Handle of the layer set as the global default
+static GLOBAL_HANDLE: Mutex<Option<WASMLayerHandle>> = Mutex::new(None);
+
+/// Keep the handle of the global layer for the exported functions
+pub(crate) fn set_global_handle(handle: WASMLayerHandle) {
+    *GLOBAL_HANDLE.lock().expect("global handle") = Some(handle);
+}
+
+/// Set the maximal level of the global layer from its name, e.g. `"debug"`, in any case
+///
+/// Throws if the level is not one of `trace`, `debug`, `info`, `warn` or `error`.
+#[wasm_bindgen]
+pub fn set_log_level_js(level: &str) -> Result<(), JsValue> {
+    let level: tracing::Level = level
+        .parse()
+        .map_err(|_| JsValue::from_str(&format!("tracing-wasm: unknown level {:?}", level)))?;
+    if let Some(handle) = &*GLOBAL_HANDLE.lock().expect("global handle") {
+        handle.set_max_level(level);
+    }
+    Ok(())
+}
+
+/// Set whether the global layer displays events and spans in the browser console, timings are unaffected
+#[wasm_bindgen]
+pub fn set_console_enabled_js(enabled: bool) {
+    if let Some(handle) = &*GLOBAL_HANDLE.lock().expect("global handle") {
+        handle.set_console_enabled(enabled);
+    }
+}
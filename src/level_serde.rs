@@ -0,0 +1,106 @@
+//! Serialize levels by their name, and deserialize them from their name in any case
+
+use core::str::FromStr;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+use tracing::level_filters::LevelFilter;
+
+fn parse<E: Error>(name: &str) -> Result<tracing::Level, E> {
+    tracing::Level::from_str(name).map_err(E::custom)
+}
+
+pub(crate) fn serialize<S: Serializer>(
+    level: &tracing::Level,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(level.as_str())
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<tracing::Level, D::Error> {
+    parse(&String::deserialize(deserializer)?)
+}
+
+/// For optional levels
+pub(crate) mod option {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        level: &Option<tracing::Level>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match level {
+            Some(level) => serializer.serialize_some(level.as_str()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<tracing::Level>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|name| parse(&name))
+            .transpose()
+    }
+}
+
+/// For `(target, level)` directives
+pub(crate) mod directives {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        directives: &[(String, LevelFilter)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(directives.iter().map(|(target, level)| {
+            (
+                target,
+                level.into_level().map_or("OFF", |level| level.as_str()),
+            )
+        }))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(String, LevelFilter)>, D::Error> {
+        Vec::<(String, String)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(target, name)| {
+                Ok((
+                    target,
+                    LevelFilter::from_str(&name).map_err(D::Error::custom)?,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// For optional sets of levels
+pub(crate) mod option_set {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        levels: &Option<Vec<tracing::Level>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match levels {
+            Some(levels) => serializer.serialize_some(
+                &levels
+                    .iter()
+                    .map(|level| level.as_str())
+                    .collect::<Vec<_>>(),
+            ),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<tracing::Level>>, D::Error> {
+        Option::<Vec<String>>::deserialize(deserializer)?
+            .map(|names| names.iter().map(|name| parse(name)).collect())
+            .transpose()
+    }
+}
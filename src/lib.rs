@@ -1,22 +1,71 @@
+use core::cell::{Cell, RefCell};
 use core::fmt::{self, Write};
-use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::collections::BTreeMap;
+#[cfg(feature = "console")]
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
+use tracing::level_filters::LevelFilter;
 use tracing::Subscriber;
 use tracing::{
-    dispatcher::SetGlobalDefaultError,
+    dispatcher::{DefaultGuard, SetGlobalDefaultError},
     field::{Field, Visit},
 };
 use tracing_subscriber::layer::*;
 use tracing_subscriber::registry::*;
 
+#[cfg(any(target_arch = "wasm32", feature = "console"))]
 use wasm_bindgen::prelude::*;
 
+#[cfg(feature = "console")]
+mod batch;
+#[cfg(feature = "capture")]
+mod capture;
+#[cfg(feature = "capture")]
+pub use capture::{CapturedEvent, CapturingLayer};
+#[cfg(feature = "dom-sink")]
+mod dom;
+#[cfg(feature = "dom-sink")]
+use dom::{DomElement, DomSink};
+#[cfg(feature = "indexeddb-sink")]
+mod indexeddb;
+#[cfg(all(feature = "indexeddb-sink", target_arch = "wasm32"))]
+pub use indexeddb::export_indexeddb_logs;
+#[cfg(feature = "indexeddb-sink")]
+use indexeddb::IndexedDbSink;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod host;
+#[cfg(feature = "js-api")]
+mod js_api;
+#[cfg(feature = "js-api")]
+pub use js_api::{set_console_enabled_js, set_log_level_js};
+#[cfg(feature = "serde")]
+mod level_serde;
+mod panic;
+pub use panic::install_panic_hook;
+#[cfg(feature = "timings")]
+mod timings;
+#[cfg(feature = "console")]
+use batch::*;
+#[cfg(not(target_arch = "wasm32"))]
+use host::*;
+#[cfg(feature = "timings")]
+use timings::*;
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    fn date_now() -> f64;
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "console"))]
 #[wasm_bindgen]
 extern "C" {
-    #[wasm_bindgen(js_namespace = performance)]
-    fn mark(a: &str);
-    #[wasm_bindgen(catch, js_namespace = performance)]
-    fn measure(name: String, startMark: String) -> Result<(), JsValue>;
     #[wasm_bindgen(js_namespace = console, js_name = log)]
     fn log1(message: String);
     #[wasm_bindgen(js_namespace = console, js_name = log)]
@@ -25,9 +74,70 @@ extern "C" {
     fn log3(message1: &str, message2: &str, message3: &str);
     #[wasm_bindgen(js_namespace = console, js_name = log)]
     fn log4(message1: String, message2: &str, message3: &str, message4: &str);
+    #[wasm_bindgen(variadic, js_namespace = console, js_name = log)]
+    fn log_variadic(items: &js_sys::Array);
+    #[wasm_bindgen(variadic, js_namespace = console, js_name = debug)]
+    fn debug_variadic(items: &js_sys::Array);
+    #[wasm_bindgen(variadic, js_namespace = console, js_name = info)]
+    fn info_variadic(items: &js_sys::Array);
+    #[wasm_bindgen(variadic, js_namespace = console, js_name = warn)]
+    fn warn_variadic(items: &js_sys::Array);
+    #[wasm_bindgen(variadic, js_namespace = console, js_name = error)]
+    fn error_variadic(items: &js_sys::Array);
+    #[wasm_bindgen(variadic, js_namespace = console, js_name = trace)]
+    fn trace_variadic(items: &js_sys::Array);
+    #[wasm_bindgen(js_namespace = console)]
+    fn table(data: &JsValue);
+    #[wasm_bindgen(js_namespace = console)]
+    fn count(label: &str);
+    #[wasm_bindgen(js_namespace = console)]
+    fn group(label: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = groupCollapsed)]
+    fn group_collapsed(label: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = groupEnd)]
+    fn group_end();
+    #[wasm_bindgen(js_namespace = console, js_name = debug)]
+    fn debug1(message: String);
+    #[wasm_bindgen(js_namespace = console, js_name = debug)]
+    fn debug3(message1: &str, message2: &str, message3: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = debug)]
+    fn debug4(message1: String, message2: &str, message3: &str, message4: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = info)]
+    fn info1(message: String);
+    #[wasm_bindgen(js_namespace = console, js_name = info)]
+    fn info3(message1: &str, message2: &str, message3: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = info)]
+    fn info4(message1: String, message2: &str, message3: &str, message4: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = warn)]
+    fn warn1(message: String);
+    #[wasm_bindgen(js_namespace = console, js_name = warn)]
+    fn warn3(message1: &str, message2: &str, message3: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = warn)]
+    fn warn4(message1: String, message2: &str, message3: &str, message4: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn error1(message: String);
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn error3(message1: &str, message2: &str, message3: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn error4(message1: String, message2: &str, message3: &str, message4: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = trace)]
+    fn trace1(message: String);
+    #[wasm_bindgen(js_namespace = console, js_name = trace)]
+    fn trace3(message1: &str, message2: &str, message3: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = trace)]
+    fn trace4(message1: String, message2: &str, message3: &str, message4: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = assert)]
+    fn assert1(condition: bool, message: String);
+    #[wasm_bindgen(js_namespace = console, js_name = assert)]
+    fn assert3(condition: bool, message1: &str, message2: &str, message3: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = assert)]
+    fn assert4(condition: bool, message1: String, message2: &str, message3: &str, message4: &str);
+    #[wasm_bindgen(variadic, js_namespace = console, js_name = assert)]
+    fn assert_variadic(condition: bool, items: &js_sys::Array);
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod test {
     use super::*;
 
@@ -41,9 +151,71 @@ mod test {
             config,
             WASMLayerConfig {
                 report_logs_in_timings: true,
+                report_spans_in_timings: true,
                 report_logs_in_console: true,
                 use_console_color: true,
+                use_ansi_color: false,
+                auto_console_color: false,
+                console_colors: ConsoleColors::default(),
+                show_origin: true,
+                show_target: false,
+                output_format: ConsoleOutputFormat::Human,
+                sink: None,
+                show_span_context: false,
+                show_span_fields: false,
+                console_methods: ConsoleMethodMap::default(),
+                error_as_assert: false,
+                debug_uses_log: false,
+                show_timestamp: false,
+                group_spans: ConsoleGroupMode::Off,
+                structured_console: false,
+                table_for_spans: false,
                 max_level: tracing::Level::TRACE,
+                target_directives: Vec::new(),
+                max_field_len: None,
+                max_message_len: None,
+                redacted_fields: Default::default(),
+                sampling: Vec::new(),
+                console_rate_limit: None,
+                trace_on: None,
+                clock: None,
+                log_span_lifecycle: false,
+                mark_prefix: String::new(),
+                measure_detail: false,
+                structured_measure_names: false,
+                count_duplicates: false,
+                require_message: false,
+                single_line: false,
+                flush_suppressed_every: None,
+                group_by_target: false,
+                show_sequence: false,
+                console_max_level: None,
+                timings_max_level: None,
+                strip_path_prefix: PathPrefix::Keep,
+                respect_no_color: true,
+                level_label_style: LevelLabelStyle::Full,
+                batch_console: false,
+                field_formatter: None,
+                quote_strings: true,
+                emit_init_mark: true,
+                log_span_busy_time: false,
+                message_prefix: String::new(),
+                message_prefix_position: MessagePrefixPosition::BeforeLevel,
+                timings_excluded_targets: Vec::new(),
+                always_console_from: None,
+                enabled_levels: None,
+                on_event_hook: None,
+                measure_name_template: MeasureNameTemplate::Full,
+                events_as_marks: false,
+                #[cfg(feature = "dom-sink")]
+                dom_sink: None,
+                inherit_span_fields: false,
+                show_span_relative_time: false,
+                max_group_depth: None,
+                show_wall_clock: false,
+                flamegraph_compatible: false,
+                #[cfg(feature = "indexeddb-sink")]
+                indexeddb_sink: None,
             }
         )
     }
@@ -56,6 +228,20 @@ mod test {
         let config = builder.build();
 
         assert_eq!(config.report_logs_in_timings, false);
+        assert_eq!(config.report_spans_in_timings, true);
+    }
+
+    #[test]
+    fn test_set_report_events_and_spans_in_timings() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder
+            .set_report_events_in_timings(true)
+            .set_report_spans_in_timings(false);
+
+        let config = builder.build();
+
+        assert_eq!(config.report_logs_in_timings, true);
+        assert_eq!(config.report_spans_in_timings, false);
     }
 
     #[test]
@@ -91,6 +277,44 @@ mod test {
         assert_eq!(config.use_console_color, true);
     }
 
+    #[test]
+    fn test_set_console_config_with_ansi_color() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_console_config(ConsoleConfig::ReportWithAnsiColor);
+
+        let config = builder.build();
+
+        assert_eq!(config.report_logs_in_console, true);
+        assert_eq!(config.use_console_color, false);
+        assert_eq!(config.use_ansi_color, true);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_set_console_config_with_auto_color() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_console_config(ConsoleConfig::ReportWithAutoColor);
+
+        let config = builder.build();
+
+        assert_eq!(config.report_logs_in_console, true);
+        assert_eq!(config.auto_console_color, true);
+        // there is no browser console on the host
+        assert_eq!(WASMLayer::new(config).config.use_console_color, false);
+    }
+
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_format_event_ansi() {
+        let config = WASMLayerConfigBuilder::new().set_show_origin(false).build();
+        let output = record_events(
+            move |event, _| format_event(&config, event).ansi(),
+            || tracing::error!("failed"),
+        );
+
+        assert_eq!(output[0], "\x1b[31mERROR\x1b[0m failed");
+    }
+
     #[test]
     fn test_default_config_log_level() {
         let builder = WASMLayerConfigBuilder::new();
@@ -109,362 +333,6786 @@ mod test {
 
         assert_eq!(config.max_level, tracing::Level::WARN);
     }
-}
 
-pub enum ConsoleConfig {
-    NoReporting,
-    ReportWithoutConsoleColor,
-    ReportWithConsoleColor,
-}
+    #[test]
+    fn test_set_console_colors() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_console_colors(ConsoleColors {
+            error: "color: white; background: darkred".to_string(),
+            ..ConsoleColors::default()
+        });
 
-pub struct WASMLayerConfigBuilder {
-    /// Log events will be marked and measured so they appear in performance Timings
-    report_logs_in_timings: bool,
-    /// Log events will be logged to the browser console
-    report_logs_in_console: bool,
-    /// Only relevant if report_logs_in_console is true, this will use color style strings in the console.
-    use_console_color: bool,
-    /// Log events will be reported from this level -- Default is ALL (TRACE)
-    max_level: tracing::Level,
-}
+        let config = builder.build();
 
-impl WASMLayerConfigBuilder {
-    pub fn new() -> WASMLayerConfigBuilder {
-        WASMLayerConfigBuilder::default()
+        assert_eq!(
+            config.console_colors.for_level(&tracing::Level::ERROR),
+            "color: white; background: darkred"
+        );
+        assert_eq!(
+            config.console_colors.for_level(&tracing::Level::TRACE),
+            "color: dodgerblue; background: #444"
+        );
     }
 
-    /// Set whether events should appear in performance Timings
-    pub fn set_report_logs_in_timings(
-        &mut self,
-        report_logs_in_timings: bool,
-    ) -> &mut WASMLayerConfigBuilder {
-        self.report_logs_in_timings = report_logs_in_timings;
-        self
+    #[test]
+    fn test_set_show_origin() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_show_origin(false);
+
+        let config = builder.build();
+
+        assert_eq!(config.show_origin, false);
     }
 
-    /// Set the maximal level on which events should be displayed
-    pub fn set_max_level(&mut self, max_level: tracing::Level) -> &mut WASMLayerConfigBuilder {
-        self.max_level = max_level;
-        self
+    #[test]
+    fn test_set_show_target() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_show_target(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.show_target, true);
     }
 
-    /// Set if and how events should be displayed in the browser console
-    pub fn set_console_config(
-        &mut self,
-        console_config: ConsoleConfig,
-    ) -> &mut WASMLayerConfigBuilder {
-        match console_config {
-            ConsoleConfig::NoReporting => {
-                self.report_logs_in_console = false;
-                self.use_console_color = false;
-            }
-            ConsoleConfig::ReportWithoutConsoleColor => {
-                self.report_logs_in_console = true;
-                self.use_console_color = false;
-            }
-            ConsoleConfig::ReportWithConsoleColor => {
-                self.report_logs_in_console = true;
-                self.use_console_color = true;
-            }
-        }
+    #[test]
+    fn test_set_output_format() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_output_format(ConsoleOutputFormat::Json);
 
-        self
+        let config = builder.build();
+
+        assert_eq!(config.output_format, ConsoleOutputFormat::Json);
     }
 
-    /// Build the WASMLayerConfig
-    pub fn build(&self) -> WASMLayerConfig {
-        WASMLayerConfig {
-            report_logs_in_timings: self.report_logs_in_timings,
-            report_logs_in_console: self.report_logs_in_console,
-            use_console_color: self.use_console_color,
-            max_level: self.max_level,
-        }
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_object_output_format_on_host() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_show_origin(false)
+            .set_output_format(ConsoleOutputFormat::Object)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || tracing::info!(items = 3, "cart"));
+
+        // there are no JS objects to inspect outside of wasm
+        assert_eq!(
+            host::take_calls(),
+            vec![r#"console.info("INFO  cart items = 3;")"#]
+        );
     }
-}
 
-impl Default for WASMLayerConfigBuilder {
-    fn default() -> WASMLayerConfigBuilder {
-        WASMLayerConfigBuilder {
-            report_logs_in_timings: true,
-            report_logs_in_console: true,
-            use_console_color: true,
-            max_level: tracing::Level::TRACE,
-        }
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_fmt_output_format() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_report_spans_in_timings(false)
+            .set_output_format(ConsoleOutputFormat::Fmt)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", method = "GET").in_scope(|| {
+                tracing::info_span!("auth").in_scope(|| {
+                    tracing::info!(target: "app::handler", id = 3, "got request");
+                });
+            });
+            tracing::warn!(target: "app", retries = 2);
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.info(" INFO request{method=\"GET\"}:auth: app::handler: got request id=3")"#,
+                r#"console.warn(" WARN app: retries=2")"#,
+            ]
+        );
     }
-}
 
-#[derive(Debug, PartialEq)]
-pub struct WASMLayerConfig {
-    report_logs_in_timings: bool,
-    report_logs_in_console: bool,
-    use_console_color: bool,
-    max_level: tracing::Level,
-}
+    #[test]
+    fn test_set_show_span_context() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_show_span_context(true);
 
-impl core::default::Default for WASMLayerConfig {
-    fn default() -> Self {
-        WASMLayerConfig {
-            report_logs_in_timings: true,
-            report_logs_in_console: true,
-            use_console_color: true,
-            max_level: tracing::Level::TRACE,
-        }
+        let config = builder.build();
+
+        assert_eq!(config.show_span_context, true);
     }
-}
 
-/// Implements [tracing_subscriber::layer::Layer] which uses [wasm_bindgen] for marking and measuring with `window.performance`
-pub struct WASMLayer {
-    last_event_id: AtomicUsize,
-    config: WASMLayerConfig,
-}
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_show_span_context() {
+        let console_output = |show_span_context| {
+            let config = WASMLayerConfigBuilder::new()
+                .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+                .set_report_events_in_timings(false)
+                .set_report_spans_in_timings(false)
+                .set_show_origin(false)
+                .set_show_span_context(show_span_context)
+                .build();
+            let subscriber = Registry::default().with(WASMLayer::new(config));
 
-impl WASMLayer {
-    pub fn new(config: WASMLayerConfig) -> Self {
-        WASMLayer {
-            last_event_id: AtomicUsize::new(0),
-            config,
-        }
+            host::take_calls();
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info_span!("request").in_scope(|| {
+                    tracing::info_span!("db").in_scope(|| tracing::info!("query"));
+                });
+            });
+            host::take_calls()
+        };
+
+        assert_eq!(
+            console_output(false),
+            vec![r#"console.info("INFO  query")"#]
+        );
+        assert_eq!(
+            console_output(true),
+            vec![r#"console.info("INFO  request > db: query")"#]
+        );
     }
-}
 
-impl core::default::Default for WASMLayer {
-    fn default() -> Self {
-        WASMLayer::new(WASMLayerConfig::default())
+    #[test]
+    fn test_set_show_span_fields() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_show_span_fields(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.show_span_fields, true);
     }
-}
 
-#[cfg(not(feature = "mark-with-rayon-thread-index"))]
-#[inline]
-fn thread_display_suffix() -> &'static str {
-    ""
-}
-#[cfg(feature = "mark-with-rayon-thread-index")]
-fn thread_display_suffix() -> String {
-    let mut message = " #".to_string();
-    match rayon::current_thread_index() {
-        Some(idx) => message.push_str(&format!("{}", idx)),
-        None => message.push_str("main"),
+    #[cfg(feature = "console")]
+    #[test]
+    fn test_set_error_as_assert() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_error_as_assert(true);
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.console_method(&tracing::Level::ERROR),
+            ConsoleMethod::Assert
+        );
+        assert_eq!(
+            config.console_method(&tracing::Level::WARN),
+            ConsoleMethod::Warn
+        );
+
+        builder.set_error_as_assert(false);
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.console_method(&tracing::Level::ERROR),
+            ConsoleMethod::Error
+        );
     }
-    message
-}
 
-#[cfg(not(feature = "mark-with-rayon-thread-index"))]
-fn mark_name(id: &tracing::Id) -> String {
-    format!("t{:x}", id.into_u64())
-}
-#[cfg(feature = "mark-with-rayon-thread-index")]
-fn mark_name(id: &tracing::Id) -> String {
-    format!(
-        "t{:x}-{}",
-        id.into_u64(),
-        rayon::current_thread_index().unwrap_or(999)
-    )
-}
+    #[cfg(feature = "console")]
+    #[test]
+    fn test_set_debug_uses_log() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_debug_uses_log(true);
 
-impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for WASMLayer {
-    fn enabled(&self, metadata: &tracing::Metadata<'_>, _: Context<'_, S>) -> bool {
-        let level = metadata.level();
-        level <= &self.config.max_level
+        let config = builder.build();
+
+        assert_eq!(
+            config.console_method(&tracing::Level::DEBUG),
+            ConsoleMethod::Log
+        );
+        assert_eq!(
+            config.console_method(&tracing::Level::TRACE),
+            ConsoleMethod::Debug
+        );
+
+        builder.set_debug_uses_log(false);
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.console_method(&tracing::Level::DEBUG),
+            ConsoleMethod::Debug
+        );
     }
 
-    fn on_new_span(
-        &self,
-        attrs: &tracing::span::Attributes<'_>,
-        id: &tracing::Id,
-        ctx: Context<'_, S>,
-    ) {
-        let mut new_debug_record = StringRecorder::new();
-        attrs.record(&mut new_debug_record);
+    /// Clock which only moves when told to
+    #[derive(Clone, Default)]
+    struct ManualClock(std::sync::Arc<std::sync::Mutex<f64>>);
 
-        if let Some(span_ref) = ctx.span(id) {
-            span_ref
-                .extensions_mut()
-                .insert::<StringRecorder>(new_debug_record);
+    impl ManualClock {
+        fn set(&self, now_ms: f64) {
+            *self.0.lock().unwrap() = now_ms;
         }
     }
 
-    /// doc: Notifies this layer that a span with the given Id recorded the given values.
-    fn on_record(&self, id: &tracing::Id, values: &tracing::span::Record<'_>, ctx: Context<'_, S>) {
-        if let Some(span_ref) = ctx.span(id) {
-            if let Some(debug_record) = span_ref.extensions_mut().get_mut::<StringRecorder>() {
-                values.record(debug_record);
-            }
+    impl Clock for ManualClock {
+        fn now_ms(&self) -> f64 {
+            *self.0.lock().unwrap()
         }
     }
 
-    // /// doc: Notifies this layer that a span with the ID span recorded that it follows from the span with the ID follows.
-    // fn on_follows_from(&self, _span: &tracing::Id, _follows: &tracing::Id, ctx: Context<'_, S>) {}
-    /// doc: Notifies this layer that an event has occurred.
-    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
-        if self.config.report_logs_in_timings || self.config.report_logs_in_console {
-            let mut recorder = StringRecorder::new();
-            event.record(&mut recorder);
-            let meta = event.metadata();
-            let level = meta.level();
-            if self.config.report_logs_in_console {
-                let origin = meta
-                    .file()
-                    .and_then(|file| meta.line().map(|ln| format!("{}:{}", file, ln)))
-                    .unwrap_or_default();
-
-                if self.config.use_console_color {
-                    log4(
-                        format!(
-                            "%c{}%c {}{}%c{}",
-                            level,
-                            origin,
-                            thread_display_suffix(),
-                            recorder,
-                        ),
-                        match *level {
-                            tracing::Level::TRACE => "color: dodgerblue; background: #444",
-                            tracing::Level::DEBUG => "color: lawngreen; background: #444",
-                            tracing::Level::INFO => "color: whitesmoke; background: #444",
-                            tracing::Level::WARN => "color: orange; background: #444",
-                            tracing::Level::ERROR => "color: red; background: #444",
-                        },
-                        "color: gray; font-style: italic",
-                        "color: inherit",
-                    );
-                } else {
-                    log1(format!(
-                        "{} {}{} {}",
-                        level,
-                        origin,
-                        thread_display_suffix(),
-                        recorder,
-                    ));
-                }
-            }
-            if self.config.report_logs_in_timings {
-                let mark_name = format!(
-                    "c{:x}",
-                    self.last_event_id
-                        .fetch_add(1, core::sync::atomic::Ordering::Relaxed)
-                );
-                // mark and measure so you can see a little blip in the profile
-                mark(&mark_name);
-                let _ = measure(
-                    format!(
-                        "{} {}{} {}",
-                        level,
-                        meta.module_path().unwrap_or("..."),
-                        thread_display_suffix(),
-                        recorder,
-                    ),
-                    mark_name,
-                );
-            }
-        }
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_set_clock() {
+        let clock = ManualClock::default();
+        clock.set(1234.5);
+        let config = WASMLayerConfigBuilder::new()
+            .set_clock(clock)
+            .set_show_timestamp(true)
+            .set_show_origin(false)
+            .build();
+
+        let output = record_events(
+            move |event, _| format_event(&config, event).plain(),
+            || tracing::info!("tick"),
+        );
+
+        assert_eq!(output[0], "1234.500 INFO  tick");
     }
-    /// doc: Notifies this layer that a span with the given ID was entered.
-    fn on_enter(&self, id: &tracing::Id, _ctx: Context<'_, S>) {
-        mark(&mark_name(id));
+
+    #[test]
+    fn test_set_show_wall_clock() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_show_wall_clock(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.show_wall_clock, true);
     }
-    /// doc: Notifies this layer that the span with the given ID was exited.
-    fn on_exit(&self, id: &tracing::Id, ctx: Context<'_, S>) {
-        if let Some(span_ref) = ctx.span(id) {
-            let meta = span_ref.metadata();
-            if let Some(debug_record) = span_ref.extensions().get::<StringRecorder>() {
-                let _ = measure(
-                    format!(
-                        "\"{}\"{} {} {}",
-                        meta.name(),
-                        thread_display_suffix(),
-                        meta.module_path().unwrap_or("..."),
-                        debug_record,
-                    ),
-                    mark_name(id),
-                );
-            } else {
-                let _ = measure(
-                    format!(
-                        "\"{}\"{} {}",
-                        meta.name(),
-                        thread_display_suffix(),
-                        meta.module_path().unwrap_or("..."),
-                    ),
-                    mark_name(id),
+
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_show_wall_clock() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_show_wall_clock(true)
+            .set_show_origin(false)
+            .build();
+
+        let output = record_events(
+            move |event, _| format_event(&config, event).plain(),
+            || tracing::info!("tick"),
+        );
+
+        // `Date.now()` is the epoch outside of a JS environment
+        assert_eq!(output[0], "1970-01-01T00:00:00.000Z INFO  tick");
+    }
+
+    #[test]
+    fn test_iso_8601() {
+        assert_eq!(iso_8601(1_700_000_000_123.0), "2023-11-14T22:13:20.123Z");
+        assert_eq!(iso_8601(951_782_400_000.0), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_console_rate_limit() {
+        let clock = ManualClock::default();
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_show_origin(false)
+            .set_console_rate_limit(2)
+            .set_clock(clock.clone())
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for idx in 0..5 {
+                tracing::info!(idx);
+            }
+            clock.set(1000.0);
+            tracing::info!(idx = 5);
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.info("INFO   idx = 0;")"#,
+                r#"console.info("INFO   idx = 1;")"#,
+                r#"console.warn("tracing-wasm: 3 messages suppressed by the console rate limit")"#,
+                r#"console.info("INFO   idx = 5;")"#,
+            ]
+        );
+    }
+
+    #[cfg(feature = "timings")]
+    #[test]
+    fn test_set_mark_prefix() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_mark_prefix("myapp-");
+
+        let config = builder.build();
+
+        assert_eq!(config.mark_prefix, "myapp-");
+        assert!(mark_name(&config.mark_prefix, &tracing::Id::from_u64(26)).starts_with("myapp-t1a"));
+        let long_prefix = "a-prefix-which-does-not-fit-on-the-stack-".repeat(2);
+        assert!(mark_name(&long_prefix, &tracing::Id::from_u64(26)).starts_with(&long_prefix));
+    }
+
+    #[test]
+    fn test_set_measure_detail() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_measure_detail(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.measure_detail, true);
+    }
+
+    #[cfg(feature = "timings")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_measure_detail() {
+        let measures = |measure_detail| {
+            let mut config = quiet_config();
+            config.report_logs_in_timings = true;
+            config.report_spans_in_timings = true;
+            config.measure_detail = measure_detail;
+            let mut layer = WASMLayer::new(config);
+            layer.timings.available = true;
+            let subscriber = Registry::default().with(layer);
+
+            host::take_calls();
+            let span_mark = tracing::subscriber::with_default(subscriber, || {
+                let span = tracing::info_span!("render", frame = 3);
+                span.in_scope(|| tracing::info!(cached = true, "drawn"));
+                format!("\"t{:x}", span.id().unwrap().into_u64())
+            });
+            // span ids depend on the spans of the other tests
+            host::take_calls()
+                .into_iter()
+                .filter(|call| call.starts_with("performance.measure"))
+                .map(|call| call.replace(&span_mark, "\"t1"))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            measures(false),
+            vec![
+                r#"performance.measure("INFO tracing_wasm::test  drawn cached = true;", "c0")"#,
+                r#"performance.measure("\"render\" tracing_wasm::test   frame = 3;", "t1", "t1-end")"#,
+            ]
+        );
+        assert_eq!(
+            measures(true),
+            vec![
+                r#"performance.measure("INFO tracing_wasm::test  drawn cached = true;", {start: "c0", detail: {level: "INFO", target: "tracing_wasm::test", message: "drawn", fields: {cached: "true"}}})"#,
+                r#"performance.measure("\"render\" tracing_wasm::test", {start: "t1", end: "t1-end", detail: {level: "INFO", target: "tracing_wasm::test", message: "render", fields: {frame: "3"}}})"#,
+            ]
+        );
+    }
+
+    #[cfg(feature = "timings")]
+    #[test]
+    fn test_measure_detail_schema() {
+        let config = WASMLayerConfigBuilder::new().build();
+        let output = record_events(
+            move |event, _| {
+                let mut recorder = StringRecorder::new(&config);
+                event.record(&mut recorder);
+                let detail = MeasureDetail::new(event.metadata(), recorder.message.as_deref());
+                (
+                    detail.level,
+                    detail.target.to_string(),
+                    detail.file.map(str::to_string),
+                    detail.line.is_some(),
+                    detail.message.map(str::to_string),
+                    recorder.fields.clone(),
+                )
+            },
+            || tracing::warn!(target: "app::net", retries = 2, "slow response"),
+        );
+
+        assert_eq!(
+            output[0],
+            (
+                "WARN",
+                "app::net".to_string(),
+                Some("src/lib.rs".to_string()),
+                true,
+                Some("slow response".to_string()),
+                vec![("retries", "2".to_string())]
+            )
+        );
+    }
+
+    #[test]
+    fn test_set_structured_measure_names() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_structured_measure_names(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.structured_measure_names, true);
+    }
+
+    #[cfg(feature = "timings")]
+    #[test]
+    fn test_structured_measure_name() {
+        let output = record_events(
+            |event, recorder| {
+                let meta = event.metadata();
+                structured_measure_name(
+                    &["event", meta.level().as_str(), meta.target(), "app|db"],
+                    &recorder.structured_fields(),
+                )
+            },
+            || tracing::info!(target: "app", query = "a;b|c", "ran"),
+        );
+
+        assert_eq!(
+            output[0],
+            r#"event|INFO|app|app\|db|message=ran;query="a\;b\|c""#
+        );
+    }
+
+    #[test]
+    fn test_set_count_duplicates() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_count_duplicates(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.count_duplicates, true);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_count_duplicates() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_show_origin(false)
+            .set_count_duplicates(true)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for attempt in 0..2 {
+                tracing::warn!(target: "app", attempt, "retrying");
+            }
+            tracing::warn!(target: "app", "giving up");
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.warn("WARN  retrying attempt = 0;")"#,
+                r#"console.count("WARN app retrying")"#,
+                r#"console.count("WARN app retrying")"#,
+                r#"console.warn("WARN  giving up")"#,
+                r#"console.count("WARN app giving up")"#,
+            ]
+        );
+    }
+
+    #[cfg(feature = "console")]
+    #[test]
+    fn test_seen_events_forget_least_recent() {
+        let mut seen = SeenEvents::default();
+        assert!(seen.insert("first"));
+        for idx in 1..MAX_SEEN_EVENTS {
+            assert!(seen.insert(&idx.to_string()));
+        }
+        // seen again, so "1" is the least recent now
+        assert!(!seen.insert("first"));
+        assert!(seen.insert("overflow"));
+
+        assert_eq!(seen.last_seen.len(), MAX_SEEN_EVENTS);
+        assert!(!seen.insert("first"));
+        assert!(seen.insert("1"));
+    }
+
+    #[test]
+    fn test_set_require_message() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_require_message(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.require_message, true);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_require_message() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_show_origin(false)
+            .set_require_message(true)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(frame_ms = 16);
+            tracing::info!(frame_ms = 16, "slow frame");
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![r#"console.info("INFO  slow frame frame_ms = 16;")"#]
+        );
+    }
+
+    #[test]
+    fn test_set_single_line() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_single_line(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.single_line, true);
+    }
+
+    #[test]
+    fn test_single_line() {
+        let config = WASMLayerConfigBuilder::new().set_single_line(true).build();
+        let output = record_events(
+            move |event, _| {
+                let mut recorder = StringRecorder::new(&config);
+                event.record(&mut recorder);
+                recorder.to_string()
+            },
+            || tracing::info!(pair = ?(1, 2), next = 3, "line one\nline two"),
+        );
+
+        assert_eq!(output[0], " line one ⏎ line two pair = (1, 2); next = 3;");
+    }
+
+    #[test]
+    fn test_set_log_span_lifecycle() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_log_span_lifecycle(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.log_span_lifecycle, true);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_log_span_lifecycle() {
+        let clock = ManualClock::default();
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_spans_in_timings(false)
+            .set_log_span_lifecycle(true)
+            .set_clock(clock.clone())
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("work").in_scope(|| clock.set(12.34));
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.debug("→ enter \"work\"")"#,
+                r#"console.debug("← exit \"work\" (12.3ms)")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_log_span_busy_time() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_log_span_busy_time(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.log_span_busy_time, true);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_log_span_busy_time() {
+        let clock = ManualClock::default();
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_spans_in_timings(false)
+            .set_log_span_busy_time(true)
+            .set_clock(clock.clone())
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        tracing::subscriber::with_default(subscriber, || {
+            // two async tasks polled in turn
+            let fetch = tracing::info_span!("fetch");
+            let render = tracing::info_span!("render");
+            fetch.in_scope(|| clock.set(2.0));
+            render.in_scope(|| clock.set(10.0));
+            clock.set(30.0);
+            fetch.in_scope(|| clock.set(33.0));
+            drop(fetch);
+            render.in_scope(|| clock.set(40.0));
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.debug("■ close \"fetch\" time.busy=5.0ms time.idle=28.0ms")"#,
+                r#"console.debug("■ close \"render\" time.busy=15.0ms time.idle=25.0ms")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_message_prefix() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_message_prefix("[checkout]");
+
+        let config = builder.build();
+
+        assert_eq!(config.message_prefix, "[checkout]");
+    }
+
+    #[test]
+    fn test_set_message_prefix_position() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_message_prefix_position(MessagePrefixPosition::AfterLevel);
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.message_prefix_position,
+            MessagePrefixPosition::AfterLevel
+        );
+    }
+
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_message_prefix() {
+        let output = |position| {
+            let config = WASMLayerConfigBuilder::new()
+                .set_show_origin(false)
+                .set_message_prefix("[checkout]")
+                .set_message_prefix_position(position)
+                .build();
+            record_events(
+                move |event, _| format_event(&config, event).plain(),
+                || tracing::info!("paid"),
+            )
+        };
+
+        assert_eq!(
+            output(MessagePrefixPosition::BeforeLevel),
+            vec!["[checkout] INFO  paid"]
+        );
+        assert_eq!(
+            output(MessagePrefixPosition::AfterLevel),
+            vec!["INFO  [checkout] paid"]
+        );
+    }
+
+    #[test]
+    fn test_set_always_console_from() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_always_console_from(tracing::Level::ERROR);
+
+        let config = builder.build();
+
+        assert_eq!(config.always_console_from, Some(tracing::Level::ERROR));
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_always_console_from() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::NoReporting)
+            .set_report_events_in_timings(false)
+            .set_show_origin(false)
+            .set_always_console_from(tracing::Level::ERROR)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!("slow response");
+            tracing::error!("request failed");
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![r#"console.error("ERROR  request failed")"#]
+        );
+    }
+
+    #[test]
+    fn test_set_trace_on() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_trace_on(tracing::Level::WARN);
+
+        let config = builder.build();
+
+        assert_eq!(config.trace_on, Some(tracing::Level::WARN));
+    }
+
+    #[test]
+    fn test_set_console_method_map() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_console_method_map(ConsoleMethodMap {
+            trace: ConsoleMethod::Trace,
+            info: ConsoleMethod::Info,
+            ..ConsoleMethodMap::log_only()
+        });
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.console_methods.for_level(&tracing::Level::TRACE),
+            ConsoleMethod::Trace
+        );
+        assert_eq!(
+            config.console_methods.for_level(&tracing::Level::INFO),
+            ConsoleMethod::Info
+        );
+        assert_eq!(
+            config.console_methods.for_level(&tracing::Level::ERROR),
+            ConsoleMethod::Log
+        );
+    }
+
+    #[test]
+    fn test_console_method_map_by_level() {
+        let methods = ConsoleMethodMap::by_level();
+
+        assert_eq!(
+            methods.for_level(&tracing::Level::TRACE),
+            ConsoleMethod::Debug
+        );
+        assert_eq!(
+            methods.for_level(&tracing::Level::WARN),
+            ConsoleMethod::Warn
+        );
+        assert_eq!(
+            methods.for_level(&tracing::Level::ERROR),
+            ConsoleMethod::Error
+        );
+    }
+
+    #[cfg(feature = "console")]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_console_color_by_level() {
+        let console_methods = |builder: &mut WASMLayerConfigBuilder| {
+            let config = builder
+                .set_console_config(ConsoleConfig::ReportWithConsoleColor)
+                .set_report_events_in_timings(false)
+                .build();
+            let subscriber = Registry::default().with(WASMLayer::new(config));
+
+            host::take_calls();
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info!("loaded");
+                tracing::warn!("slow response");
+                tracing::error!("request failed");
+            });
+
+            host::take_calls()
+                .into_iter()
+                .map(|call| call[..call.find('(').unwrap()].to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            console_methods(&mut WASMLayerConfigBuilder::new()),
+            vec!["console.info", "console.warn", "console.error"]
+        );
+        assert_eq!(
+            console_methods(
+                WASMLayerConfigBuilder::new().set_console_method_map(ConsoleMethodMap::log_only())
+            ),
+            vec!["console.log", "console.log", "console.log"]
+        );
+    }
+
+    #[test]
+    fn test_set_show_timestamp() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_show_timestamp(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.show_timestamp, true);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_show_timestamp() {
+        let console_output = |show_timestamp| {
+            let clock = ManualClock::default();
+            clock.set(1234.5);
+            let config = WASMLayerConfigBuilder::new()
+                .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+                .set_report_events_in_timings(false)
+                .set_report_spans_in_timings(false)
+                .set_show_origin(false)
+                .set_clock(clock)
+                .set_show_timestamp(show_timestamp)
+                .build();
+            let subscriber = Registry::default().with(WASMLayer::new(config));
+
+            host::take_calls();
+            tracing::subscriber::with_default(subscriber, || tracing::info!("tick"));
+            host::take_calls()
+        };
+
+        assert_eq!(console_output(false), vec![r#"console.info("INFO  tick")"#]);
+        assert_eq!(
+            console_output(true),
+            vec![r#"console.info("1234.500 INFO  tick")"#]
+        );
+    }
+
+    #[test]
+    fn test_set_group_spans() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_group_spans(ConsoleGroupMode::Collapsed);
+
+        let config = builder.build();
+
+        assert_eq!(config.group_spans, ConsoleGroupMode::Collapsed);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_group_spans() {
+        let console_output = |group_spans| {
+            let config = WASMLayerConfigBuilder::new()
+                .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+                .set_report_events_in_timings(false)
+                .set_report_spans_in_timings(false)
+                .set_show_origin(false)
+                .set_group_spans(group_spans)
+                .build();
+            let subscriber = Registry::default().with(WASMLayer::new(config));
+
+            host::take_calls();
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info_span!("request").in_scope(|| tracing::info!("handled"));
+            });
+            host::take_calls()
+        };
+
+        assert_eq!(
+            console_output(ConsoleGroupMode::Off),
+            vec![r#"console.info("INFO  handled")"#]
+        );
+        assert_eq!(
+            console_output(ConsoleGroupMode::Expanded),
+            vec![
+                r#"console.group("request")"#,
+                r#"console.info("INFO  handled")"#,
+                "console.groupEnd()"
+            ]
+        );
+        assert_eq!(
+            console_output(ConsoleGroupMode::Collapsed),
+            vec![
+                r#"console.groupCollapsed("request")"#,
+                r#"console.info("INFO  handled")"#,
+                "console.groupEnd()"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_max_group_depth() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_max_group_depth(2);
+
+        let config = builder.build();
+
+        assert_eq!(config.max_group_depth, Some(2));
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_max_group_depth() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_report_spans_in_timings(false)
+            .set_show_origin(false)
+            .set_group_spans(ConsoleGroupMode::Expanded)
+            .set_max_group_depth(1)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("outer").in_scope(|| {
+                tracing::info_span!("inner").in_scope(|| tracing::info!("deep"));
+            });
+            tracing::info_span!("next").in_scope(|| {});
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.group("outer")"#,
+                r#"console.info("INFO  deep")"#,
+                "console.groupEnd()",
+                r#"console.group("next")"#,
+                "console.groupEnd()",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_target_directive() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder
+            .set_max_level(tracing::Level::INFO)
+            .add_target_directive("my_crate", tracing::Level::DEBUG)
+            .add_target_directive("my_crate::noisy", tracing::Level::ERROR)
+            .add_target_directive("dependency", tracing::Level::WARN);
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.target_max_level("my_crate"),
+            Some(LevelFilter::DEBUG)
+        );
+        assert_eq!(
+            config.target_max_level("my_crate::ui"),
+            Some(LevelFilter::DEBUG)
+        );
+        assert_eq!(
+            config.target_max_level("my_crate::noisy::inner"),
+            Some(LevelFilter::ERROR)
+        );
+        assert_eq!(
+            config.target_max_level("dependency"),
+            Some(LevelFilter::WARN)
+        );
+        assert_eq!(config.target_max_level("other"), None);
+    }
+
+    #[test]
+    fn test_set_directives_from_str() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder
+            .set_directives_from_str("my_crate=debug, wgpu=WARN,info,")
+            .unwrap();
+
+        let config = builder.build();
+
+        assert_eq!(config.max_level, tracing::Level::INFO);
+        assert_eq!(
+            config.target_directives,
+            vec![
+                ("my_crate".to_string(), LevelFilter::DEBUG),
+                ("wgpu".to_string(), LevelFilter::WARN),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_directives_from_str_off() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder
+            .set_directives_from_str("noisy_crate=off,my_crate=debug")
+            .unwrap();
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.target_max_level("noisy_crate::io"),
+            Some(LevelFilter::OFF)
+        );
+        assert_eq!(config.target_max_level("other"), None);
+
+        builder.set_directives_from_str("OFF").unwrap();
+
+        let config = builder.build();
+
+        assert_eq!(config.target_max_level("other"), Some(LevelFilter::OFF));
+        assert_eq!(
+            config.target_max_level("my_crate::ui"),
+            Some(LevelFilter::DEBUG)
+        );
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_off_directive_disables_target() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder
+            .set_console_config(ConsoleConfig::NoReporting)
+            .set_report_events_in_timings(false)
+            .set_directives_from_str("noisy_crate=off")
+            .unwrap();
+        let layer = WASMLayer::new(builder.build());
+        let subscriber = Registry::default().with(layer);
+
+        let enabled = tracing::subscriber::with_default(subscriber, || {
+            (
+                tracing::enabled!(target: "noisy_crate", tracing::Level::ERROR),
+                tracing::enabled!(target: "my_crate", tracing::Level::ERROR),
+            )
+        });
+
+        assert_eq!(enabled, (false, true));
+    }
+
+    #[test]
+    fn test_set_directives_from_str_malformed() {
+        let mut builder = WASMLayerConfigBuilder::new();
+
+        let error = match builder.set_directives_from_str("my_crate=debug,wgpu=loud") {
+            Ok(_) => panic!("malformed directives should not parse"),
+            Err(error) => error,
+        };
+
+        assert_eq!(error.to_string(), "invalid tracing directive \"wgpu=loud\"");
+        assert!(builder.build().target_directives.is_empty());
+        assert!(builder.set_directives_from_str("=debug").is_err());
+    }
+
+    #[test]
+    fn test_handle_set_max_level() {
+        let layer = WASMLayer::new(quiet_config());
+        let handle = layer.handle();
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(tracing::enabled!(tracing::Level::TRACE));
+
+            handle.set_max_level(tracing::Level::WARN);
+
+            assert_eq!(handle.max_level(), tracing::Level::WARN);
+            assert!(!tracing::enabled!(tracing::Level::INFO));
+            assert!(tracing::enabled!(tracing::Level::ERROR));
+        });
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_handle_set_console_enabled() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_show_origin(false)
+            .build();
+        let layer = WASMLayer::new(config);
+        let handle = layer.handle();
+        let subscriber = Registry::default().with(layer);
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            handle.set_console_enabled(false);
+            tracing::info!("hidden");
+            handle.set_console_enabled(true);
+            tracing::info!("shown");
+        });
+
+        assert_eq!(host::take_calls(), vec![r#"console.info("INFO  shown")"#]);
+    }
+
+    #[test]
+    fn test_set_enabled_levels() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_enabled_levels([tracing::Level::ERROR, tracing::Level::DEBUG]);
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.enabled_levels,
+            Some(vec![tracing::Level::ERROR, tracing::Level::DEBUG])
+        );
+    }
+
+    #[test]
+    fn test_enabled_levels() {
+        let mut config = quiet_config();
+        config.enabled_levels = Some(vec![tracing::Level::ERROR, tracing::Level::DEBUG]);
+        let layer = WASMLayer::new(config);
+        assert_eq!(
+            Layer::<Registry>::max_level_hint(&layer),
+            Some(LevelFilter::DEBUG)
+        );
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(tracing::enabled!(tracing::Level::ERROR));
+            assert!(!tracing::enabled!(tracing::Level::WARN));
+            assert!(!tracing::enabled!(tracing::Level::INFO));
+            assert!(tracing::enabled!(tracing::Level::DEBUG));
+            assert!(!tracing::enabled!(tracing::Level::TRACE));
+        });
+    }
+
+    #[test]
+    fn test_set_on_event_hook() {
+        let errors = Arc::new(AtomicUsize::new(0));
+        let counted = errors.clone();
+        let mut config = WASMLayerConfigBuilder::new()
+            .set_on_event_hook(move |event| {
+                if event.level == tracing::Level::ERROR {
+                    assert_eq!(event.target, "tracing_wasm::test");
+                    assert_eq!(event.message, Some("request failed"));
+                    assert_eq!(event.fields, &[("status", "500".to_string())]);
+                    counted.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .build();
+        config.report_logs_in_console = false;
+        config.report_logs_in_timings = false;
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("request sent");
+            tracing::error!(status = 500, "request failed");
+        });
+
+        assert_eq!(errors.load(Ordering::Relaxed), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_config() {
+        let config: WASMLayerConfig = serde_json::from_str(
+            r#"{
+                "report_logs_in_timings": false,
+                "max_level": "warn",
+                "target_directives": [["my_crate", "DEBUG"]],
+                "trace_on": "Error",
+                "console_methods": {"trace": "Log", "debug": "Debug", "info": "Info", "warn": "Warn", "error": "Error"}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.report_logs_in_timings, false);
+        assert_eq!(config.report_spans_in_timings, true);
+        assert_eq!(config.max_level, tracing::Level::WARN);
+        assert_eq!(
+            config.target_max_level("my_crate::db"),
+            Some(LevelFilter::DEBUG)
+        );
+        assert_eq!(config.trace_on, Some(tracing::Level::ERROR));
+        assert_eq!(
+            config.console_methods.for_level(&tracing::Level::WARN),
+            ConsoleMethod::Warn
+        );
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#""max_level":"WARN""#));
+        assert_eq!(
+            serde_json::from_str::<WASMLayerConfig>(&json).unwrap(),
+            config
+        );
+        assert!(serde_json::from_str::<WASMLayerConfig>(r#"{"max_level": "loud"}"#).is_err());
+    }
+
+    #[test]
+    fn test_max_level_hint() {
+        use tracing::level_filters::LevelFilter;
+
+        let mut config = quiet_config();
+        config.max_level = tracing::Level::WARN;
+        let layer = WASMLayer::new(config.clone());
+        assert_eq!(
+            Layer::<Registry>::max_level_hint(&layer),
+            Some(LevelFilter::WARN)
+        );
+
+        config.target_directives = vec![
+            ("my_crate".to_string(), LevelFilter::DEBUG),
+            ("wgpu".to_string(), LevelFilter::ERROR),
+        ];
+        let layer = WASMLayer::new(config);
+        assert_eq!(
+            Layer::<Registry>::max_level_hint(&layer),
+            Some(LevelFilter::DEBUG)
+        );
+
+        layer.handle().set_max_level(tracing::Level::TRACE);
+        assert_eq!(
+            Layer::<Registry>::max_level_hint(&layer),
+            Some(LevelFilter::TRACE)
+        );
+    }
+
+    #[test]
+    fn test_set_measure_name_template() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_measure_name_template(MeasureNameTemplate::NameOnly);
+
+        let config = builder.build();
+
+        assert_eq!(config.measure_name_template, MeasureNameTemplate::NameOnly);
+    }
+
+    #[cfg(feature = "timings")]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_measure_name_template() {
+        let measure_names = |template| {
+            let mut config = quiet_config();
+            config.report_spans_in_timings = true;
+            config.measure_name_template = template;
+            let mut layer = WASMLayer::new(config);
+            layer.timings.available = true;
+            let subscriber = Registry::default().with(layer);
+
+            host::take_calls();
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info_span!("render", frame = 3).in_scope(|| {});
+            });
+            host::take_calls()
+                .into_iter()
+                .filter_map(|call| {
+                    call.strip_prefix("performance.measure(")
+                        .and_then(|args| args.split(", ").next().map(str::to_string))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            measure_names(MeasureNameTemplate::NameOnly),
+            vec![r#""\"render\"""#]
+        );
+        assert_eq!(
+            measure_names(MeasureNameTemplate::NameAndModule),
+            vec![r#""\"render\" tracing_wasm::test""#]
+        );
+        assert_eq!(
+            measure_names(MeasureNameTemplate::NameAndFields),
+            vec![r#""\"render\"   frame = 3;""#]
+        );
+    }
+
+    #[test]
+    fn test_name_only_skips_span_fields() {
+        let mut config = quiet_config();
+        config.measure_name_template = MeasureNameTemplate::NameOnly;
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        let fields = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("render", frame = 3, cached = tracing::field::Empty);
+            span.record("cached", true);
+            tracing::dispatcher::get_default(|dispatch| {
+                let registry = dispatch.downcast_ref::<Registry>().unwrap();
+                let span_ref = registry.span(&span.id().unwrap()).unwrap();
+                let extensions = span_ref.extensions();
+                let recorders = extensions.get::<PerLayer<StringRecorder>>().unwrap();
+                recorders.0[0].1.fields.len()
+            })
+        });
+
+        assert_eq!(fields, 0);
+
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_measure_name_template(MeasureNameTemplate::NameOnly);
+
+        assert!(builder.build().records_span_fields());
+
+        builder.set_console_config(ConsoleConfig::NoReporting);
+
+        assert!(!builder.build().records_span_fields());
+
+        builder.set_measure_detail(true);
+
+        assert!(builder.build().records_span_fields());
+    }
+
+    #[test]
+    fn test_set_flamegraph_compatible() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_flamegraph_compatible(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.flamegraph_compatible, true);
+    }
+
+    #[cfg(feature = "timings")]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_flamegraph_compatible() {
+        let mut config = quiet_config();
+        config.report_spans_in_timings = true;
+        config.flamegraph_compatible = true;
+        config.message_prefix = "[app]".to_string();
+        let mut layer = WASMLayer::new(config);
+        layer.timings.available = true;
+        let subscriber = Registry::default().with(layer);
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("render", frame = 3).in_scope(|| {});
+        });
+
+        let measures: Vec<_> = host::take_calls()
+            .into_iter()
+            .filter_map(|call| {
+                call.strip_prefix("performance.measure(")
+                    .and_then(|args| args.split(", ").next().map(str::to_string))
+            })
+            .collect();
+        assert_eq!(measures, vec![r#""render""#]);
+    }
+
+    #[test]
+    fn test_set_events_as_marks() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_events_as_marks(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.events_as_marks, true);
+    }
+
+    #[test]
+    fn test_set_inherit_span_fields() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_inherit_span_fields(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.inherit_span_fields, true);
+    }
+
+    #[test]
+    fn test_set_show_span_relative_time() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_show_span_relative_time(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.show_span_relative_time, true);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_show_span_relative_time() {
+        let clock = ManualClock::default();
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_report_spans_in_timings(false)
+            .set_show_origin(false)
+            .set_show_span_relative_time(true)
+            .set_clock(clock.clone())
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        clock.set(100.0);
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("outside");
+            tracing::info_span!("request").in_scope(|| {
+                clock.set(112.3);
+                tracing::info!("responded");
+            });
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.info("INFO  outside")"#,
+                r#"console.info("+12.3ms INFO  responded")"#,
+            ]
+        );
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_inherit_span_fields() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_report_spans_in_timings(false)
+            .set_show_origin(false)
+            .set_inherit_span_fields(true)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", user = "ada", attempt = 1).in_scope(|| {
+                tracing::info!("loaded");
+                tracing::info!(attempt = 2, "retried");
+            });
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.info("INFO  loaded user = \"ada\";\nattempt = 1;")"#,
+                r#"console.info("INFO  retried user = \"ada\";\nattempt = 2;")"#,
+            ]
+        );
+    }
+
+    #[cfg(feature = "indexeddb-sink")]
+    #[test]
+    fn test_set_indexeddb_sink() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_indexeddb_sink("logs", 1000);
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.indexeddb_sink,
+            Some(IndexedDbSink {
+                database_name: "logs".to_string(),
+                max_entries: 1000,
+            })
+        );
+    }
+
+    #[cfg(feature = "dom-sink")]
+    #[test]
+    fn test_set_dom_sink() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_dom_sink("log", 100);
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.dom_sink,
+            Some(DomSink {
+                element_id: "log".to_string(),
+                element: None,
+                max_nodes: 100,
+            })
+        );
+    }
+
+    #[cfg(feature = "timings")]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_events_as_marks() {
+        let mut config = quiet_config();
+        config.report_logs_in_timings = true;
+        config.events_as_marks = true;
+        let mut layer = WASMLayer::new(config);
+        layer.timings.available = true;
+        let subscriber = Registry::default().with(layer);
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(items = 3, "cart validated");
+            tracing::warn!(retries = 2);
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"performance.mark("cart validated")"#,
+                r#"performance.mark("WARN tracing_wasm::test")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_timings_excluded_target() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.add_timings_excluded_target("hyper");
+
+        let config = builder.build();
+
+        assert_eq!(config.timings_excluded_targets, vec!["hyper".to_string()]);
+    }
+
+    #[cfg(feature = "timings")]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_timings_excluded_target() {
+        let mut config = quiet_config();
+        config.report_logs_in_timings = true;
+        config.report_spans_in_timings = true;
+        config.timings_excluded_targets = vec!["hyper".to_string()];
+        let mut layer = WASMLayer::new(config);
+        layer.timings.available = true;
+        let subscriber = Registry::default().with(layer);
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!(target: "hyper::proto", "poll").in_scope(|| {
+                tracing::info!(target: "hyper::proto", "read");
+                tracing::info_span!(target: "app", "render").in_scope(|| {});
+            });
+        });
+
+        // span ids depend on the threads running the tests, so only the measure names are compared
+        let measures: Vec<_> = host::take_calls()
+            .into_iter()
+            .filter_map(|call| {
+                call.strip_prefix("performance.measure(")
+                    .and_then(|args| args.split(", ").next().map(str::to_string))
+            })
+            .collect();
+        assert_eq!(measures, vec![r#""\"render\" tracing_wasm::test ""#]);
+    }
+
+    #[cfg(feature = "timings")]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_filtered_spans_have_no_timings() {
+        let mut config = quiet_config();
+        config.report_spans_in_timings = true;
+        config.target_directives = vec![("app::noisy".to_string(), LevelFilter::WARN)];
+        let mut layer = WASMLayer::new(config);
+        layer.timings.available = true;
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!(target: "app::noisy", "poll").in_scope(|| {
+                tracing::debug_span!(target: "app::noisy", "inner").in_scope(|| {});
+            });
+            tracing::info_span!(target: "app", "render").in_scope(|| {});
+        });
+
+        // the spans of the noisy target are not even created, so the first span id is the render one
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"performance.mark("t1")"#,
+                r#"performance.mark("t1-end")"#,
+                r#"performance.measure("\"render\" tracing_wasm::test ", "t1", "t1-end")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_console_max_level() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_console_max_level(tracing::Level::INFO);
+
+        let config = builder.build();
+
+        assert_eq!(config.console_max_level, Some(tracing::Level::INFO));
+    }
+
+    #[test]
+    fn test_set_console_min_level() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_console_min_level(tracing::Level::INFO);
+
+        let config = builder.build();
+
+        assert_eq!(config.console_max_level, Some(tracing::Level::INFO));
+    }
+
+    #[test]
+    fn test_set_timings_max_level() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_timings_max_level(tracing::Level::DEBUG);
+
+        let config = builder.build();
+
+        assert_eq!(config.timings_max_level, Some(tracing::Level::DEBUG));
+    }
+
+    #[test]
+    fn test_set_timings_min_level() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_timings_min_level(tracing::Level::INFO);
+
+        let config = builder.build();
+
+        assert_eq!(config.timings_max_level, Some(tracing::Level::INFO));
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(feature = "timings")]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_console_and_timings_max_levels() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_spans_in_timings(false)
+            .set_show_origin(false)
+            .set_max_level(tracing::Level::DEBUG)
+            .set_console_max_level(tracing::Level::INFO)
+            .set_timings_max_level(tracing::Level::TRACE)
+            .build();
+        let mut layer = WASMLayer::new(config);
+        layer.timings.available = true;
+        assert_eq!(
+            Layer::<Registry>::max_level_hint(&layer),
+            Some(tracing::level_filters::LevelFilter::DEBUG)
+        );
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("shown");
+            tracing::debug!("timed");
+            tracing::trace!("filtered");
+        });
+
+        let calls = host::take_calls();
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|call| call.starts_with("console"))
+                .collect::<Vec<_>>(),
+            vec![r#"console.info("INFO  shown")"#]
+        );
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|call| call.starts_with("performance.mark"))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_set_strip_path_prefix() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_strip_path_prefix("/home/user/project/");
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.strip_path_prefix,
+            PathPrefix::Strip("/home/user/project/".to_string())
+        );
+    }
+
+    #[test]
+    fn test_set_respect_no_color() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_respect_no_color(false);
+
+        let config = builder.build();
+
+        assert_eq!(config.respect_no_color, false);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_group_field() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_show_origin(false)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(__group = "open", "checkout");
+            tracing::info!(step = 1, "cart validated");
+            tracing::info!(__group = "close");
+            tracing::info!(__group = "other", "not a command");
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.group("checkout")"#,
+                r#"console.info("INFO  cart validated step = 1;")"#,
+                "console.groupEnd()",
+                r#"console.info("INFO  not a command __group = \"other\";")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_batch_console() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_batch_console(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.batch_console, true);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_batch_console() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_show_origin(false)
+            .set_batch_console(true)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("first");
+            tracing::debug!("second");
+            assert_eq!(host::take_calls(), Vec::<String>::new());
+            tracing::warn!("third");
+            tracing::info!("fourth");
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.log("INFO  first\nDEBUG  second")"#,
+                r#"console.warn("WARN  third")"#,
+                r#"console.log("INFO  fourth")"#,
+            ]
+        );
+    }
+
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_set_field_formatter() {
+        struct ValuesOnly;
+
+        impl FieldFormatter for ValuesOnly {
+            fn format(&self, _name: &str, value: &dyn fmt::Debug) -> String {
+                format!("[{:?}]", value)
+            }
+        }
+
+        let config = WASMLayerConfigBuilder::new()
+            .set_show_origin(false)
+            .set_single_line(true)
+            .set_field_formatter(ValuesOnly)
+            .build();
+
+        let output = record_events(
+            move |event, _| format_event(&config, event).plain(),
+            || tracing::info!(user = "ada", attempts = 3, "login"),
+        );
+
+        assert_eq!(output[0], r#"INFO  login ["ada"] [3]"#);
+    }
+
+    #[test]
+    fn test_set_emit_init_mark() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_emit_init_mark(false);
+
+        let config = builder.build();
+
+        assert_eq!(config.emit_init_mark, false);
+    }
+
+    #[cfg(feature = "timings")]
+    #[test]
+    fn test_init_mark_needs_timings() {
+        let mut builder = WASMLayerConfigBuilder::new();
+
+        assert!(builder.build().marks_init());
+
+        builder.set_report_logs_in_timings(false);
+
+        assert!(builder.build().marks_init());
+
+        builder.set_report_spans_in_timings(false);
+
+        assert!(!builder.build().marks_init());
+    }
+
+    #[cfg(feature = "timings")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_mark_init() {
+        let mut config = quiet_config();
+        config.mark_prefix = "myapp-".to_string();
+        let layer = WASMLayer::new(config);
+
+        host::take_calls();
+        layer.mark_init();
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"performance.mark("myapp-wasm-layer-init")"#,
+                r#"performance.measure("myapp-wasm-layer-init")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_level_label_style() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_level_label_style(LevelLabelStyle::Short);
+
+        let config = builder.build();
+
+        assert_eq!(config.level_label_style, LevelLabelStyle::Short);
+    }
+
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_level_label_style() {
+        let output = |style| {
+            let config = WASMLayerConfigBuilder::new()
+                .set_show_origin(false)
+                .set_level_label_style(style)
+                .build();
+            record_events(
+                move |event, _| format_event(&config, event).plain(),
+                || tracing::warn!("disk almost full"),
+            )
+        };
+
+        assert_eq!(
+            output(LevelLabelStyle::Full),
+            vec!["WARN  disk almost full"]
+        );
+        assert_eq!(
+            output(LevelLabelStyle::Short),
+            vec!["WRN  disk almost full"]
+        );
+        assert_eq!(output(LevelLabelStyle::Single), vec!["W  disk almost full"]);
+        assert_eq!(output(LevelLabelStyle::Badge), vec!["●  disk almost full"]);
+    }
+
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_level_badge_styled() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_show_origin(false)
+            .set_level_label_style(LevelLabelStyle::Badge)
+            .build();
+        let output = record_events(
+            move |event, _| {
+                let formatted = format_event(&config, event);
+                (formatted.styled(), formatted.styles()[0].to_string())
+            },
+            || tracing::error!("disk full"),
+        );
+
+        assert_eq!(
+            output[0],
+            (
+                "%c●%c disk full".to_string(),
+                "color: red; background: #444".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_strip_path_prefix() {
+        let file = "/home/user/project/src/foo.rs";
+
+        assert_eq!(PathPrefix::Keep.strip(file), file);
+        assert_eq!(
+            PathPrefix::from("/home/user/project/").strip(file),
+            "src/foo.rs"
+        );
+        assert_eq!(PathPrefix::from("/elsewhere/").strip(file), file);
+        assert_eq!(PathPrefix::Auto.strip(file), "foo.rs");
+        assert_eq!(PathPrefix::Auto.strip("build.rs"), "build.rs");
+    }
+
+    #[cfg(feature = "timings")]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_shared_layer_keeps_counters() {
+        let mut config = quiet_config();
+        config.report_logs_in_timings = true;
+        let mut layer = WASMLayer::new(config);
+        layer.timings.available = true;
+        let layer = SharedWASMLayer::new(layer);
+
+        for _ in 0..2 {
+            let subscriber = Registry::default().with(layer.clone());
+            tracing::subscriber::with_default(subscriber, || tracing::info!("reloaded"));
+        }
+
+        let marks: Vec<_> = host::take_calls()
+            .into_iter()
+            .filter(|call| call.starts_with("performance.mark"))
+            .collect();
+        assert_eq!(
+            marks,
+            vec![r#"performance.mark("c0")"#, r#"performance.mark("c1")"#]
+        );
+    }
+
+    #[test]
+    fn test_shared_layer_downcasts_to_wasm_layer() {
+        let layer = SharedWASMLayer::new(WASMLayer::new(quiet_config()));
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::dispatcher::get_default(|dispatch| {
+                assert!(dispatch.downcast_ref::<WASMLayer>().is_some());
+                assert!(dispatch.downcast_ref::<SharedWASMLayer>().is_some());
+            })
+        });
+    }
+
+    #[cfg(feature = "timings")]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_reset_event_counter() {
+        let mut config = quiet_config();
+        config.report_logs_in_timings = true;
+        let mut layer = WASMLayer::new(config);
+        layer.timings.available = true;
+        let handle = layer.handle();
+        let subscriber = Registry::default().with(layer);
+
+        host::take_calls();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("home");
+            tracing::info!("home loaded");
+            assert_eq!(handle.current_event_id(), 2);
+            handle.reset_event_counter();
+            assert_eq!(handle.current_event_id(), 0);
+            tracing::info!("settings");
+        });
+
+        let marks: Vec<_> = host::take_calls()
+            .into_iter()
+            .filter(|call| call.starts_with("performance.mark"))
+            .collect();
+        assert_eq!(
+            marks,
+            vec![
+                r#"performance.mark("c0")"#,
+                r#"performance.mark("c1")"#,
+                r#"performance.mark("c0")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clone_config() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder
+            .set_console_colors(ConsoleColors {
+                info: "color: teal".to_string(),
+                ..ConsoleColors::default()
+            })
+            .add_target_directive("my_crate", tracing::Level::DEBUG);
+
+        let config = builder.clone().build();
+
+        assert_eq!(config.clone(), builder.build());
+    }
+
+    #[test]
+    fn test_set_max_field_and_message_len() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_max_field_len(4).set_max_message_len(8);
+
+        let config = builder.build();
+
+        assert_eq!(config.max_field_len, Some(4));
+        assert_eq!(config.max_message_len, Some(8));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short".to_string(), None), "short");
+        assert_eq!(truncate("short".to_string(), Some(5)), "short");
+        assert_eq!(
+            truncate("truncated".to_string(), Some(5)),
+            "trunc…(4 more bytes)"
+        );
+        // never cut a character in half
+        assert_eq!(truncate("aé".to_string(), Some(2)), "a…(2 more bytes)");
+    }
+
+    #[test]
+    fn test_add_redacted_field() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.add_redacted_field("password");
+
+        let config = builder.build();
+
+        assert_eq!(*config.redacted_fields, vec!["password".to_string()]);
+    }
+
+    #[test]
+    fn test_set_sampling() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder
+            .set_sampling("render", 10)
+            .set_sampling("render", 60)
+            .set_sampling("net", 0);
+
+        let config = builder.build();
+
+        assert_eq!(
+            config.sampling,
+            vec![("render".to_string(), 60), ("net".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_set_console_rate_limit() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_console_rate_limit(100);
+
+        let config = builder.build();
+
+        assert_eq!(config.console_rate_limit, Some(100));
+    }
+
+    #[test]
+    fn test_sampled_out_events() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::NoReporting)
+            .set_report_events_in_timings(false)
+            .set_report_spans_in_timings(false)
+            .set_sampling("app::render", 4)
+            .build();
+        let layer = WASMLayer::new(config);
+        let handle = layer.handle();
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for frame in 0..10 {
+                tracing::trace!(target: "app::render", frame);
+            }
+            tracing::info!(target: "app::net", "not sampled");
+        });
+
+        assert_eq!(handle.sampled_out_events(), 7);
+    }
+
+    #[test]
+    fn test_open_span_count() {
+        let layer = WASMLayer::new(quiet_config());
+        let handle = layer.handle();
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request");
+            tracing::info_span!("closed").in_scope(|| {});
+            let leaked = tracing::info_span!(parent: &request, "leaked");
+            core::mem::forget(leaked);
+            assert_eq!(handle.open_span_count(), 2);
+
+            drop(request);
+            // the leaked span keeps its parent open
+            assert_eq!(handle.open_span_count(), 2);
+        });
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_trace_context() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_report_spans_in_timings(false)
+            .set_show_origin(false)
+            .build();
+        let layer = WASMLayer::new(config);
+        let handle = layer.handle();
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            handle.set_trace_context("4bf92f3577b34da6a3ce929d0e0e4736", Some("00f067aa0ba902b7"));
+            tracing::info!("request");
+            tracing::info!(trace_id = "0af7651916cd43dd8448eb211c80319c", "response");
+            handle.clear_trace_context();
+            tracing::info!("done");
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.info("INFO  request trace_id = \"4bf92f3577b34da6a3ce929d0e0e4736\";\nspan_id = \"00f067aa0ba902b7\";")"#,
+                r#"console.info("INFO  response trace_id = \"0af7651916cd43dd8448eb211c80319c\";\nspan_id = \"00f067aa0ba902b7\";")"#,
+                r#"console.info("INFO  done")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_show_sequence() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_show_sequence(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.show_sequence, true);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_show_sequence() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_report_spans_in_timings(false)
+            .set_show_origin(false)
+            .set_show_sequence(true)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..2 {
+                tracing::info!("tick");
+            }
+            tracing::warn!(target: "other", "late");
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r##"console.info("#1 INFO  tick")"##,
+                r##"console.info("#2 INFO  tick")"##,
+                r##"console.warn("#3 WARN  late")"##,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_group_by_target() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_group_by_target(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.group_by_target, true);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_group_by_target() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_report_events_in_timings(false)
+            .set_report_spans_in_timings(false)
+            .set_show_origin(false)
+            .set_group_by_target(true)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app::net", "request");
+            tracing::info!(target: "app::net", "response");
+            tracing::info!(target: "app::render", "frame");
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.groupCollapsed("app::net")"#,
+                r#"console.info("INFO  request")"#,
+                r#"console.info("INFO  response")"#,
+                r#"console.groupEnd()"#,
+                r#"console.groupCollapsed("app::render")"#,
+                r#"console.info("INFO  frame")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_flush_suppressed_every() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_flush_suppressed_every(10_000);
+
+        let config = builder.build();
+
+        assert_eq!(config.flush_suppressed_every, Some(10_000));
+    }
+
+    #[cfg(feature = "console")]
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_flush_suppressed() {
+        let mut config = quiet_config();
+        config.sampling = vec![("app::render".to_string(), 4), ("app::net".to_string(), 2)];
+        let layer = WASMLayer::new(config);
+        let handle = layer.handle();
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for frame in 0..10 {
+                tracing::trace!(target: "app::render", frame);
+            }
+            for _ in 0..3 {
+                tracing::info!(target: "app::net", "request");
+            }
+        });
+        host::take_calls();
+
+        assert_eq!(
+            handle.flush_suppressed(),
+            vec![("app::net", 1), ("app::render", 7)]
+        );
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.info("tracing-wasm: suppressed events by target: app::net: 1, app::render: 7")"#
+            ]
+        );
+        assert_eq!(handle.flush_suppressed(), vec![]);
+        assert!(host::take_calls().is_empty());
+    }
+
+    #[test]
+    fn test_set_structured_console() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_structured_console(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.structured_console, true);
+    }
+
+    #[test]
+    fn test_set_table_for_spans() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_table_for_spans(true);
+
+        let config = builder.build();
+
+        assert_eq!(config.table_for_spans, true);
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_table_for_spans() {
+        let console_output = |table_for_spans| {
+            let config = WASMLayerConfigBuilder::new()
+                .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+                .set_report_events_in_timings(false)
+                .set_report_spans_in_timings(false)
+                .set_show_origin(false)
+                .set_table_for_spans(table_for_spans)
+                .build();
+            let subscriber = Registry::default().with(WASMLayer::new(config));
+
+            host::take_calls();
+            tracing::subscriber::with_default(subscriber, || {
+                let span = tracing::info_span!("request", user = "ada", attempt = 2);
+                span.in_scope(|| tracing::info!("handled"));
+                // a re-entered span is shown once
+                span.in_scope(|| {});
+                drop(span);
+                tracing::info_span!("idle").in_scope(|| {});
+            });
+            host::take_calls()
+        };
+
+        assert_eq!(
+            console_output(false),
+            vec![r#"console.info("INFO  handled")"#]
+        );
+        assert_eq!(
+            console_output(true),
+            vec![
+                r#"console.info("INFO  handled")"#,
+                r#"console.table({user: "\"ada\"", attempt: "2"})"#,
+            ]
+        );
+    }
+
+    /// Records the events it sees with a [StringRecorder] and collects the output of `f`
+    struct RecordingLayer<F, T> {
+        f: F,
+        output: std::sync::Arc<std::sync::Mutex<Vec<T>>>,
+    }
+
+    impl<S, F, T> Layer<S> for RecordingLayer<F, T>
+    where
+        S: Subscriber,
+        F: Fn(&tracing::Event<'_>, StringRecorder) -> T + 'static,
+        T: 'static,
+    {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            let mut recorder = StringRecorder::default();
+            event.record(&mut recorder);
+            self.output.lock().unwrap().push((self.f)(event, recorder));
+        }
+    }
+
+    fn record_events<F, T>(f: F, emit: impl FnOnce()) -> Vec<T>
+    where
+        F: Fn(&tracing::Event<'_>, StringRecorder) -> T + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        let output = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let subscriber = Registry::default().with(RecordingLayer {
+            f,
+            output: output.clone(),
+        });
+        tracing::subscriber::with_default(subscriber, emit);
+        let output = output.lock().unwrap().drain(..).collect();
+        output
+    }
+
+    /// Config which never calls into the JS bindings, so the layer can run on the host
+    fn quiet_config() -> WASMLayerConfig {
+        WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::NoReporting)
+            .set_report_events_in_timings(false)
+            .set_report_spans_in_timings(false)
+            .build()
+    }
+
+    /// Counts closed spans which still had a [StringRecorder] in their extensions
+    struct LeakProbeLayer {
+        closed: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+        leaked: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for LeakProbeLayer {
+        fn on_close(&self, id: tracing::Id, ctx: Context<'_, S>) {
+            let span_ref = ctx.span(&id).expect("closed span");
+            if span_ref
+                .extensions()
+                .get::<PerLayer<StringRecorder>>()
+                .is_some()
+            {
+                self.leaked
+                    .fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+            }
+            self.closed
+                .fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(feature = "console")]
+    #[cfg(feature = "timings")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_layer_on_host() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::ReportWithoutConsoleColor)
+            .set_show_origin(false)
+            .build();
+        let subscriber = Registry::default().with(WASMLayer::new(config));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request").in_scope(|| tracing::info!("handled"));
+        });
+
+        assert_eq!(
+            host::take_calls(),
+            vec![
+                r#"console.warn("tracing-wasm: performance.mark or performance.measure is unavailable, timings will not be reported")"#,
+                r#"console.info("INFO  handled")"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_as_default_with_config() {
+        let is_wasm_layer = || {
+            tracing::dispatcher::get_default(|dispatch| {
+                dispatch.is::<Layered<WASMLayer, Registry>>()
+            })
+        };
+
+        let guard = set_as_default_with_config(quiet_config());
+        assert!(is_wasm_layer());
+
+        drop(guard);
+        assert!(!is_wasm_layer());
+    }
+
+    #[test]
+    fn test_on_close_removes_extensions() {
+        let closed = std::sync::Arc::new(AtomicUsize::new(0));
+        let leaked = std::sync::Arc::new(AtomicUsize::new(0));
+        let subscriber = Registry::default()
+            .with(WASMLayer::new(quiet_config()))
+            .with(LeakProbeLayer {
+                closed: closed.clone(),
+                leaked: leaked.clone(),
+            });
+
+        tracing::subscriber::with_default(subscriber, || {
+            for idx in 0..1000 {
+                tracing::info_span!("short", idx).in_scope(|| {});
+            }
+        });
+
+        assert_eq!(closed.load(core::sync::atomic::Ordering::Relaxed), 1000);
+        assert_eq!(leaked.load(core::sync::atomic::Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_message_before_fields() {
+        let output = record_events(
+            |_, recorder| recorder.to_string(),
+            || {
+                tracing::info!(a = 1, b = "two", c = true, "hello");
+                tracing::info!(a = 1, b = "two", message = "declared last");
+                tracing::info!(a = 1, b = "two");
+            },
+        );
+
+        assert_eq!(
+            output,
+            vec![
+                " hello a = 1;\nb = \"two\";\nc = true;",
+                " \"declared last\" a = 1;\nb = \"two\";",
+                "  a = 1;\nb = \"two\";",
+            ]
+        );
+    }
+
+    /// The fields of a single event recorded with the config, on a single line
+    fn recorded_fields(config: WASMLayerConfig, emit: impl FnOnce()) -> String {
+        let output = record_events(
+            move |event, _| {
+                let mut recorder = StringRecorder::new(&config);
+                event.record(&mut recorder);
+                recorder.to_string()
+            },
+            emit,
+        );
+        output[0].clone()
+    }
+
+    #[test]
+    fn test_record_values() {
+        let cases: [(bool, fn(), &str); 6] = [
+            (true, || tracing::info!(delta = -5i64), "  delta = -5;"),
+            (true, || tracing::info!(count = 5u64), "  count = 5;"),
+            (true, || tracing::info!(ratio = 0.25f64), "  ratio = 0.25;"),
+            (true, || tracing::info!(cached = true), "  cached = true;"),
+            (
+                true,
+                || tracing::info!(name = "hello"),
+                "  name = \"hello\";",
+            ),
+            (false, || tracing::info!(name = "hello"), "  name = hello;"),
+        ];
+
+        for (quote_strings, emit, expected) in cases {
+            let config = WASMLayerConfigBuilder::new()
+                .set_quote_strings(quote_strings)
+                .build();
+            assert_eq!(recorded_fields(config, emit), expected);
+        }
+    }
+
+    /// Debug implementation which panics
+    struct PanickingDebug;
+
+    impl fmt::Debug for PanickingDebug {
+        fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+            panic!("broken Debug implementation")
+        }
+    }
+
+    /// Debug implementation which returns an error
+    struct FailingDebug;
+
+    impl fmt::Debug for FailingDebug {
+        fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+            Err(fmt::Error)
+        }
+    }
+
+    #[test]
+    fn test_record_formatting_error() {
+        let config = WASMLayerConfigBuilder::new().build();
+        let fields = recorded_fields(
+            config,
+            || tracing::info!(panicking = ?PanickingDebug, failing = ?FailingDebug, ok = 1),
+        );
+        assert_eq!(
+            fields,
+            "  panicking = <formatting error>;\nfailing = <formatting error>;\nok = 1;"
+        );
+    }
+
+    #[test]
+    fn test_set_quote_strings() {
+        let mut builder = WASMLayerConfigBuilder::new();
+        builder.set_quote_strings(false);
+
+        let config = builder.build();
+
+        assert_eq!(config.quote_strings, false);
+    }
+
+    #[test]
+    fn test_redacted_fields() {
+        let config = WASMLayerConfigBuilder::new()
+            .add_redacted_field("password")
+            .build();
+
+        let output = record_events(
+            move |event, _| {
+                let mut recorder = StringRecorder::new(&config);
+                event.record(&mut recorder);
+                recorder.to_string()
+            },
+            || tracing::info!(user = "alice", password = "hunter2", "login"),
+        );
+
+        assert_eq!(
+            output[0],
+            " login user = \"alice\";\npassword = <redacted>;"
+        );
+    }
+
+    #[derive(Debug)]
+    struct TestError(&'static str, Option<Box<TestError>>);
+
+    impl core::fmt::Display for TestError {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+
+    impl std::error::Error for TestError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.1.as_deref().map(|source| source as _)
+        }
+    }
+
+    #[test]
+    fn test_record_error_chain() {
+        let output = record_events(
+            |_, recorder| recorder.to_string(),
+            || {
+                let error = TestError(
+                    "request failed",
+                    Some(Box::new(TestError(
+                        "connection reset",
+                        Some(Box::new(TestError("socket closed", None))),
+                    ))),
+                );
+                tracing::error!(error = &error as &(dyn std::error::Error + 'static), "sync");
+            },
+        );
+
+        assert_eq!(
+            output[0],
+            " sync error = request failed\n    caused by: connection reset\n    caused by: socket closed;"
+        );
+    }
+
+    #[test]
+    fn test_recorder_reset_and_reuse() {
+        let config = WASMLayerConfigBuilder::new().build();
+        let output = record_events(
+            move |event, mut recorder| {
+                recorder.reset(&config);
+                event.record(&mut recorder);
+                let first = recorder.to_string();
+                let capacity = recorder.fields.capacity();
+                recorder.reset(&config);
+                assert!(recorder.message.is_none());
+                assert!(recorder.fields.is_empty());
+                assert_eq!(recorder.fields.capacity(), capacity);
+                assert_eq!(recorder.spare_buffers.len(), 2);
+                assert!(recorder
+                    .spare_buffers
+                    .iter()
+                    .all(|buffer| buffer.is_empty() && buffer.capacity() > 0));
+                event.record(&mut recorder);
+                assert!(recorder.spare_buffers.is_empty());
+                (first, recorder.to_string())
+            },
+            || tracing::info!(attempt = 2, "retrying"),
+        );
+
+        assert_eq!(output[0].0, " retrying attempt = 2;");
+        assert_eq!(output[0].1, output[0].0);
+    }
+
+    #[cfg(feature = "console")]
+    #[test]
+    fn test_json_event() {
+        let output = record_events(
+            |event, recorder| json_event(event.metadata(), &recorder),
+            || tracing::info!(target: "app", answer = 42, text = "a \"quote\"", "hello"),
+        );
+
+        assert!(
+            output[0].starts_with(r#"{"level":"INFO","target":"app","file":"src/lib.rs","line":"#)
+        );
+        assert!(output[0].ends_with(
+            r#","message":"hello","fields":{"answer":"42","text":"\"a \\\"quote\\\"\""}}"#
+        ));
+    }
+
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_format_event_with_target() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_show_origin(false)
+            .set_show_target(true)
+            .build();
+        let output = record_events(
+            move |event, _| format_event(&config, event).plain(),
+            || tracing::info!(target: "app::db", "connected"),
+        );
+
+        assert_eq!(output[0], "INFO app::db  connected");
+    }
+
+    #[cfg(not(feature = "mark-with-rayon-thread-index"))]
+    #[test]
+    fn test_format_event() {
+        let config = WASMLayerConfigBuilder::new().set_show_origin(false).build();
+        let output = record_events(
+            move |event, _| {
+                let formatted = format_event(&config, event);
+                (
+                    formatted.plain(),
+                    formatted.styled(),
+                    formatted.styles().len(),
+                )
+            },
+            || tracing::warn!(attempt = 2, "retrying"),
+        );
+
+        assert_eq!(
+            output[0],
+            (
+                "WARN  retrying attempt = 2;".to_string(),
+                "%cWARN%c retrying attempt = 2;".to_string(),
+                2
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_event_styles() {
+        let config = WASMLayerConfigBuilder::new()
+            .set_console_colors(ConsoleColors {
+                origin_style: "color: teal".to_string(),
+                message_style: "font-weight: bold".to_string(),
+                ..ConsoleColors::default()
+            })
+            .build();
+        let output = record_events(
+            move |event, _| {
+                format_event(&config, event)
+                    .styles()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            },
+            || tracing::info!("connected"),
+        );
+
+        assert_eq!(
+            output[0],
+            vec![
+                "color: whitesmoke; background: #444",
+                "color: teal",
+                "font-weight: bold"
+            ]
+        );
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConsoleConfig {
+    NoReporting,
+    ReportWithoutConsoleColor,
+    ReportWithConsoleColor,
+    /// Color with ANSI escape sequences rather than CSS, for consoles which render them such as Node
+    ReportWithAnsiColor,
+    /// Color with CSS in browsers, including their workers, and report without color anywhere else
+    /// such as in Node, where `%c` directives would be displayed as is
+    ReportWithAutoColor,
+}
+
+/// Format of the messages reported to the browser console
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConsoleOutputFormat {
+    /// Human readable messages, optionally styled with console colors
+    Human,
+    /// One JSON object per event with `level`, `target`, `file`, `line`, `message` and `fields`
+    Json,
+    /// Human readable messages followed by a `%o` of the fields as an expandable JS object,
+    /// human readable messages outside of wasm
+    Object,
+    /// Lines like the default format of the `tracing-subscriber` fmt layer without timestamps, which
+    /// `tracing-web` writes to the console, e.g. ` INFO request{id=3}: app::net: connected retries=2`
+    ///
+    /// This eases migrating from `tracing-web`, though the formatting of field values may differ slightly.
+    /// Console colors, the origin and the other decorations of human readable messages are not shown.
+    /// Timings are unaffected, [WASMLayerConfigBuilder::set_flamegraph_compatible] names span measures
+    /// after the bare span name.
+    Fmt,
+}
+
+/// Whether spans open a console group so the events within them are nested in the browser console
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConsoleGroupMode {
+    Off,
+    /// Use `console.group`
+    Expanded,
+    /// Use `console.groupCollapsed`
+    Collapsed,
+}
+
+#[cfg(feature = "console")]
+impl ConsoleGroupMode {
+    fn open(self, label: &str) {
+        match self {
+            ConsoleGroupMode::Off => {}
+            ConsoleGroupMode::Expanded => group(label),
+            ConsoleGroupMode::Collapsed => group_collapsed(label),
+        }
+    }
+}
+
+/// How the level of events is labelled in the browser console
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LevelLabelStyle {
+    /// `TRACE`, `DEBUG`, `INFO`, `WARN` and `ERROR`
+    Full,
+    /// `TRC`, `DBG`, `INF`, `WRN` and `ERR`
+    Short,
+    /// `T`, `D`, `I`, `W` and `E`
+    Single,
+    /// A `●` styled with the color of the level, for the most compact lines
+    ///
+    /// Levels are only told apart by their color, so messages without console colors, e.g. those of
+    /// [WASMLayerConfigBuilder::set_sink], all start with the same glyph.
+    Badge,
+}
+
+impl LevelLabelStyle {
+    fn label(self, level: &tracing::Level) -> &'static str {
+        match (self, *level) {
+            (LevelLabelStyle::Full, level) => level.as_str(),
+            (LevelLabelStyle::Short, tracing::Level::TRACE) => "TRC",
+            (LevelLabelStyle::Short, tracing::Level::DEBUG) => "DBG",
+            (LevelLabelStyle::Short, tracing::Level::INFO) => "INF",
+            (LevelLabelStyle::Short, tracing::Level::WARN) => "WRN",
+            (LevelLabelStyle::Short, tracing::Level::ERROR) => "ERR",
+            (LevelLabelStyle::Single, tracing::Level::TRACE) => "T",
+            (LevelLabelStyle::Single, tracing::Level::DEBUG) => "D",
+            (LevelLabelStyle::Single, tracing::Level::INFO) => "I",
+            (LevelLabelStyle::Single, tracing::Level::WARN) => "W",
+            (LevelLabelStyle::Single, tracing::Level::ERROR) => "E",
+            (LevelLabelStyle::Badge, _) => "●",
+        }
+    }
+}
+
+/// What the names of span measures show, unless they are structured
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MeasureNameTemplate {
+    /// `"name" module fields`
+    Full,
+    /// `"name"`, which doesn't record the fields of the span unless the console, the measure detail or
+    /// [WASMLayerConfigBuilder::set_inherit_span_fields] needs them
+    NameOnly,
+    /// `"name" module`
+    NameAndModule,
+    /// `"name" fields`
+    NameAndFields,
+}
+
+/// Where the tag of [WASMLayerConfigBuilder::set_message_prefix] is shown in console messages
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MessagePrefixPosition {
+    /// `[checkout] INFO  message`
+    BeforeLevel,
+    /// `INFO  [checkout] message`
+    AfterLevel,
+}
+
+/// What is removed from the start of file paths in the origin of events
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathPrefix {
+    /// Show file paths as they are
+    Keep,
+    /// Remove this prefix from the file paths which start with it
+    Strip(String),
+    /// Remove everything up to and including the last `src/`
+    Auto,
+}
+
+impl PathPrefix {
+    fn strip<'a>(&self, file: &'a str) -> &'a str {
+        match self {
+            PathPrefix::Keep => file,
+            PathPrefix::Strip(prefix) => file.strip_prefix(prefix.as_str()).unwrap_or(file),
+            PathPrefix::Auto => match file.rfind("src/") {
+                Some(idx) => &file[idx + "src/".len()..],
+                None => file,
+            },
+        }
+    }
+}
+
+impl From<&str> for PathPrefix {
+    fn from(prefix: &str) -> Self {
+        PathPrefix::Strip(prefix.to_string())
+    }
+}
+
+/// Identifies a layer, so several layers of one registry keep their own data in the extensions of a span
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct LayerId(usize);
+
+/// Data of one type stored in the extensions of a span by each layer
+struct PerLayer<T>(Vec<(LayerId, T)>);
+
+impl LayerId {
+    fn next() -> Self {
+        static NEXT_LAYER_ID: AtomicUsize = AtomicUsize::new(0);
+        LayerId(NEXT_LAYER_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn get<'e, T: Send + Sync + 'static>(self, extensions: &'e Extensions<'_>) -> Option<&'e T> {
+        let per_layer = extensions.get::<PerLayer<T>>()?;
+        per_layer
+            .0
+            .iter()
+            .find(|(id, _)| *id == self)
+            .map(|(_, value)| value)
+    }
+
+    fn get_mut<'e, T: Send + Sync + 'static>(
+        self,
+        extensions: &'e mut ExtensionsMut<'_>,
+    ) -> Option<&'e mut T> {
+        let per_layer = extensions.get_mut::<PerLayer<T>>()?;
+        per_layer
+            .0
+            .iter_mut()
+            .find(|(id, _)| *id == self)
+            .map(|(_, value)| value)
+    }
+
+    /// Store the value of this layer, replacing the previous one
+    fn insert<T: Send + Sync + 'static>(self, extensions: &mut ExtensionsMut<'_>, value: T) {
+        match extensions.get_mut::<PerLayer<T>>() {
+            Some(per_layer) => match per_layer.0.iter_mut().find(|(id, _)| *id == self) {
+                Some((_, previous)) => *previous = value,
+                None => per_layer.0.push((self, value)),
+            },
+            None => extensions.insert(PerLayer(vec![(self, value)])),
+        }
+    }
+
+    fn remove<T: Send + Sync + 'static>(self, extensions: &mut ExtensionsMut<'_>) -> Option<T> {
+        let per_layer = extensions.get_mut::<PerLayer<T>>()?;
+        let index = per_layer.0.iter().position(|(id, _)| *id == self)?;
+        let (_, value) = per_layer.0.swap_remove(index);
+        if per_layer.0.is_empty() {
+            extensions.remove::<PerLayer<T>>();
+        }
+        Some(value)
+    }
+}
+
+#[cfg(feature = "console")]
+/// Number of console groups opened by a span which haven't been closed yet
+struct OpenConsoleGroups(usize);
+
+#[cfg(any(feature = "console", feature = "capture"))]
+/// Time in milliseconds at which a span was created, according to the clock of the config
+struct CreatedAt(f64);
+
+#[cfg(feature = "console")]
+/// Time in milliseconds at which a span was last entered, according to the clock of the config
+struct EnteredAt(f64);
+
+#[cfg(feature = "console")]
+/// Marks a span whose fields are shown with `console.table` when it closes, instead of measured in timings
+struct TabledSpan;
+
+#[cfg(feature = "console")]
+/// Time in milliseconds a span spent entered so far, according to the clock of the config
+struct BusyTime {
+    created_at: f64,
+    busy: f64,
+    /// When the span was entered, if it is currently entered
+    entered_at: Option<f64>,
+    /// Number of times the span is currently entered, e.g. on several threads
+    entered: usize,
+}
+
+/// Method of the browser console used to report events
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConsoleMethod {
+    Log,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    /// Also prints a JS stack trace
+    Trace,
+    /// A failed `console.assert`, which is displayed as an error with a JS stack trace
+    Assert,
+}
+
+#[cfg(feature = "console")]
+impl ConsoleMethod {
+    fn log1(self, message: String) {
+        match self {
+            ConsoleMethod::Log => log1(message),
+            ConsoleMethod::Debug => debug1(message),
+            ConsoleMethod::Info => info1(message),
+            ConsoleMethod::Warn => warn1(message),
+            ConsoleMethod::Error => error1(message),
+            ConsoleMethod::Trace => trace1(message),
+            ConsoleMethod::Assert => assert1(false, message),
+        }
+    }
+
+    fn log3(self, message1: &str, message2: &str, message3: &str) {
+        match self {
+            ConsoleMethod::Log => log3(message1, message2, message3),
+            ConsoleMethod::Debug => debug3(message1, message2, message3),
+            ConsoleMethod::Info => info3(message1, message2, message3),
+            ConsoleMethod::Warn => warn3(message1, message2, message3),
+            ConsoleMethod::Error => error3(message1, message2, message3),
+            ConsoleMethod::Trace => trace3(message1, message2, message3),
+            ConsoleMethod::Assert => assert3(false, message1, message2, message3),
+        }
+    }
+
+    fn log4(self, message1: String, message2: &str, message3: &str, message4: &str) {
+        match self {
+            ConsoleMethod::Log => log4(message1, message2, message3, message4),
+            ConsoleMethod::Debug => debug4(message1, message2, message3, message4),
+            ConsoleMethod::Info => info4(message1, message2, message3, message4),
+            ConsoleMethod::Warn => warn4(message1, message2, message3, message4),
+            ConsoleMethod::Error => error4(message1, message2, message3, message4),
+            ConsoleMethod::Trace => trace4(message1, message2, message3, message4),
+            ConsoleMethod::Assert => assert4(false, message1, message2, message3, message4),
+        }
+    }
+
+    fn log_variadic(self, items: &js_sys::Array) {
+        match self {
+            ConsoleMethod::Log => log_variadic(items),
+            ConsoleMethod::Debug => debug_variadic(items),
+            ConsoleMethod::Info => info_variadic(items),
+            ConsoleMethod::Warn => warn_variadic(items),
+            ConsoleMethod::Error => error_variadic(items),
+            ConsoleMethod::Trace => trace_variadic(items),
+            ConsoleMethod::Assert => assert_variadic(false, items),
+        }
+    }
+}
+
+/// Console method used for each level
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConsoleMethodMap {
+    pub trace: ConsoleMethod,
+    pub debug: ConsoleMethod,
+    pub info: ConsoleMethod,
+    pub warn: ConsoleMethod,
+    pub error: ConsoleMethod,
+}
+
+impl ConsoleMethodMap {
+    /// The console method of the same level for each level, and `console.debug` for TRACE,
+    /// so devtools filter and group events by level, with or without console color
+    ///
+    /// This is the default map.
+    pub fn by_level() -> Self {
+        ConsoleMethodMap {
+            trace: ConsoleMethod::Debug,
+            debug: ConsoleMethod::Debug,
+            info: ConsoleMethod::Info,
+            warn: ConsoleMethod::Warn,
+            error: ConsoleMethod::Error,
+        }
+    }
+
+    /// `console.log` for every level, the output of earlier versions, which devtools can't filter by level
+    pub fn log_only() -> Self {
+        ConsoleMethodMap {
+            trace: ConsoleMethod::Log,
+            debug: ConsoleMethod::Log,
+            info: ConsoleMethod::Log,
+            warn: ConsoleMethod::Log,
+            error: ConsoleMethod::Log,
+        }
+    }
+
+    /// Get the console method for the given level
+    pub fn for_level(&self, level: &tracing::Level) -> ConsoleMethod {
+        match *level {
+            tracing::Level::TRACE => self.trace,
+            tracing::Level::DEBUG => self.debug,
+            tracing::Level::INFO => self.info,
+            tracing::Level::WARN => self.warn,
+            tracing::Level::ERROR => self.error,
+        }
+    }
+}
+
+impl core::default::Default for ConsoleMethodMap {
+    fn default() -> Self {
+        ConsoleMethodMap::by_level()
+    }
+}
+
+thread_local! {
+    /// Recorder reused by the events of this thread, see [StringRecorder::reset]
+    static SCRATCH_RECORDER: RefCell<StringRecorder> = RefCell::new(StringRecorder::default());
+}
+
+thread_local! {
+    /// An event is being reported on this thread, see [ReportingEvent]
+    static REPORTING_EVENT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks that an event is being reported on this thread until it is dropped, even by a panic
+struct ReportingEvent;
+
+impl ReportingEvent {
+    /// Returns `None` if an event is already being reported on this thread
+    fn enter() -> Option<ReportingEvent> {
+        if REPORTING_EVENT.with(|reporting| reporting.replace(true)) {
+            None
+        } else {
+            Some(ReportingEvent)
+        }
+    }
+}
+
+impl Drop for ReportingEvent {
+    fn drop(&mut self) {
+        REPORTING_EVENT.with(|reporting| reporting.set(false));
+    }
+}
+
+/// A function registered as a [ConsoleSink] on the current thread
+struct RegisteredSink {
+    id: usize,
+    function: js_sys::Function,
+    /// Number of [ConsoleSink::new] calls sharing this registration
+    refs: usize,
+}
+
+thread_local! {
+    static CONSOLE_SINKS: RefCell<Vec<RegisteredSink>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A JS function called with `(message, level)` for each event instead of logging to the browser console
+///
+/// `js_sys::Function` can't be shared across threads, so the function is kept in a registry of the thread
+/// which created the sink, under an id unique to the process. Events reported on any other thread fall back
+/// to the browser console. The function is unregistered when the last clone of every sink created for it
+/// is dropped on that thread, a sink dropped on another thread leaves it registered.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConsoleSink(Arc<ConsoleSinkId>);
+
+#[derive(Debug, PartialEq)]
+struct ConsoleSinkId(usize);
+
+impl ConsoleSink {
+    /// Register the function, a function which is already registered on this thread shares its registration,
+    /// so configs rebuilt with the same function don't grow the registry
+    pub fn new(function: js_sys::Function) -> Self {
+        static NEXT_CONSOLE_SINK_ID: AtomicUsize = AtomicUsize::new(0);
+        CONSOLE_SINKS.with(|sinks| {
+            let mut sinks = sinks.borrow_mut();
+            let id = match sinks.iter_mut().find(|sink| sink.function == function) {
+                Some(sink) => {
+                    sink.refs += 1;
+                    sink.id
+                }
+                None => {
+                    let id = NEXT_CONSOLE_SINK_ID.fetch_add(1, Ordering::Relaxed);
+                    sinks.push(RegisteredSink {
+                        id,
+                        function,
+                        refs: 1,
+                    });
+                    id
+                }
+            };
+            ConsoleSink(Arc::new(ConsoleSinkId(id)))
+        })
+    }
+
+    #[cfg(feature = "console")]
+    fn call(&self, message: String, level: &tracing::Level) {
+        let id = (self.0).0;
+        let function = CONSOLE_SINKS.with(|sinks| {
+            sinks
+                .borrow()
+                .iter()
+                .find(|sink| sink.id == id)
+                .map(|sink| sink.function.clone())
+        });
+        match function {
+            Some(function) => {
+                let _ = function.call2(
+                    &JsValue::NULL,
+                    &JsValue::from(message),
+                    &JsValue::from(level.as_str()),
+                );
+            }
+            None => log1(message),
+        }
+    }
+}
+
+impl Drop for ConsoleSinkId {
+    fn drop(&mut self) {
+        // the registry may already be destroyed when the thread exits
+        let _ = CONSOLE_SINKS.try_with(|sinks| {
+            let mut sinks = sinks.borrow_mut();
+            if let Some(idx) = sinks.iter().position(|sink| sink.id == self.0) {
+                sinks[idx].refs -= 1;
+                if sinks[idx].refs == 0 {
+                    sinks.swap_remove(idx);
+                }
+            }
+        });
+    }
+}
+
+/// Source of the current time in milliseconds, used for timestamps and the console rate limit
+pub trait Clock: Send + Sync {
+    fn now_ms(&self) -> f64;
+}
+
+/// The default [Clock], reading `performance.now()`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PerformanceClock;
+
+impl Clock for PerformanceClock {
+    fn now_ms(&self) -> f64 {
+        performance_now()
+    }
+}
+
+/// A custom [Clock] of a config, configs are only equal if they share the same clock
+#[derive(Clone)]
+struct ClockRef(Arc<dyn Clock>);
+
+impl fmt::Debug for ClockRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ClockRef(..)")
+    }
+}
+
+impl PartialEq for ClockRef {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Formats each recorded field of events and spans in the console and in measure names
+pub trait FieldFormatter: Send + Sync {
+    /// Format a field including its separator, `value` displays the recorded value with `{:?}`
+    fn format(&self, name: &str, value: &dyn fmt::Debug) -> String;
+}
+
+/// The default [FieldFormatter], formatting fields like `name = value;`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultFieldFormatter;
+
+impl FieldFormatter for DefaultFieldFormatter {
+    fn format(&self, name: &str, value: &dyn fmt::Debug) -> String {
+        format!("{} = {:?};", name, value)
+    }
+}
+
+/// A custom [FieldFormatter] of a config, configs are only equal if they share the same formatter
+#[derive(Clone)]
+struct FieldFormatterRef(Arc<dyn FieldFormatter>);
+
+impl fmt::Debug for FieldFormatterRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FieldFormatterRef(..)")
+    }
+}
+
+impl PartialEq for FieldFormatterRef {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A recorded value, already formatted with `{:?}` and possibly truncated or redacted
+struct RecordedValue<'a>(&'a str);
+
+impl<'a> fmt::Debug for RecordedValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// An event passed to the hook of [WASMLayerConfigBuilder::set_on_event_hook]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EventInfo<'a> {
+    pub level: tracing::Level,
+    pub target: &'a str,
+    pub message: Option<&'a str>,
+    /// The other fields formatted with `{:?}`, in the order they were recorded
+    pub fields: &'a [(&'static str, String)],
+}
+
+/// A hook of a config called on every event, configs are only equal if they share the same hook
+#[derive(Clone)]
+struct EventHookRef(Arc<dyn Fn(&EventInfo<'_>) + Send + Sync>);
+
+impl fmt::Debug for EventHookRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventHookRef(..)")
+    }
+}
+
+impl PartialEq for EventHookRef {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// CSS styles applied to each part of the events when console color is enabled
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ConsoleColors {
+    pub trace: String,
+    pub debug: String,
+    pub info: String,
+    pub warn: String,
+    pub error: String,
+    /// Style of the file and line of events
+    pub origin_style: String,
+    /// Style of the rest of the message, including its fields
+    pub message_style: String,
+}
+
+impl ConsoleColors {
+    /// Get the CSS style of the level label for the given level
+    pub fn for_level(&self, level: &tracing::Level) -> &str {
+        match *level {
+            tracing::Level::TRACE => &self.trace,
+            tracing::Level::DEBUG => &self.debug,
+            tracing::Level::INFO => &self.info,
+            tracing::Level::WARN => &self.warn,
+            tracing::Level::ERROR => &self.error,
+        }
+    }
+}
+
+impl core::default::Default for ConsoleColors {
+    fn default() -> Self {
+        ConsoleColors {
+            trace: "color: dodgerblue; background: #444".to_string(),
+            debug: "color: lawngreen; background: #444".to_string(),
+            info: "color: whitesmoke; background: #444".to_string(),
+            warn: "color: orange; background: #444".to_string(),
+            error: "color: red; background: #444".to_string(),
+            origin_style: "color: gray; font-style: italic".to_string(),
+            message_style: "color: inherit".to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct WASMLayerConfigBuilder {
+    /// Log events will be marked and measured so they appear in performance Timings
+    report_logs_in_timings: bool,
+    /// Spans will be marked on enter and measured on exit so they appear in performance Timings
+    report_spans_in_timings: bool,
+    /// Log events will be logged to the browser console
+    report_logs_in_console: bool,
+    /// Only relevant if report_logs_in_console is true, this will use color style strings in the console.
+    use_console_color: bool,
+    /// Only relevant if report_logs_in_console is true, this will use ANSI escape sequences in the console.
+    use_ansi_color: bool,
+    /// Only relevant if report_logs_in_console is true, use_console_color is decided by the layer depending on the environment
+    auto_console_color: bool,
+    /// Only relevant if use_console_color is true, the CSS style used for each level
+    console_colors: ConsoleColors,
+    /// Only relevant if report_logs_in_console is true, this will prefix events with their file:line origin
+    show_origin: bool,
+    /// Only relevant if report_logs_in_console is true, this will prefix events with their target
+    show_target: bool,
+    /// Only relevant if report_logs_in_console is true, the format of the messages in the console
+    output_format: ConsoleOutputFormat,
+    /// Only relevant if report_logs_in_console is true, events are passed to this JS function instead of the console
+    sink: Option<ConsoleSink>,
+    /// Only relevant if report_logs_in_console is true, this will prefix events with the spans they occurred in
+    show_span_context: bool,
+    /// Only relevant if report_logs_in_console is true, this will follow events with the fields recorded on their spans
+    show_span_fields: bool,
+    /// Only relevant if report_logs_in_console is true, the console method used for each level
+    console_methods: ConsoleMethodMap,
+    /// Only relevant if report_logs_in_console is true, ERROR events are failed `console.assert`s whatever the console method map
+    error_as_assert: bool,
+    /// Only relevant if report_logs_in_console is true, DEBUG events use `console.log` whatever the console method map
+    debug_uses_log: bool,
+    /// Only relevant if report_logs_in_console is true, this will prefix events with milliseconds from performance.now()
+    show_timestamp: bool,
+    /// Only relevant if report_logs_in_console is true, spans will open a console group while entered
+    group_spans: ConsoleGroupMode,
+    /// Only relevant if report_logs_in_console is true, event fields are passed to the console as separate arguments
+    structured_console: bool,
+    /// Only relevant if report_logs_in_console is true, spans with fields are shown with console.table on exit instead of measured
+    table_for_spans: bool,
+    /// Log events will be reported from this level -- Default is ALL (TRACE)
+    max_level: tracing::Level,
+    /// Targets starting with the given prefix will be reported from the given level instead of max_level
+    target_directives: Vec<(String, LevelFilter)>,
+    /// Field values longer than this many bytes will be truncated
+    max_field_len: Option<usize>,
+    /// Messages longer than this many bytes will be truncated
+    max_message_len: Option<usize>,
+    /// Fields with these names will be displayed as `<redacted>` in both events and spans
+    redacted_fields: Vec<String>,
+    /// Only one in this many events of targets starting with the given prefix will be reported
+    sampling: Vec<(String, u32)>,
+    /// Only relevant if report_logs_in_console is true, at most this many events are displayed each second
+    console_rate_limit: Option<u32>,
+    /// Only relevant if report_logs_in_console is true, events of this level or more severe are followed by a console.trace
+    trace_on: Option<tracing::Level>,
+    /// Clock used instead of [PerformanceClock] for timestamps and the console rate limit
+    clock: Option<ClockRef>,
+    /// Only relevant if report_logs_in_console is true, entering and exiting spans is displayed at DEBUG styling
+    log_span_lifecycle: bool,
+    /// Only relevant if report_logs_in_timings or report_spans_in_timings is true, the names of marks start with this
+    mark_prefix: String,
+    /// Only relevant if report_logs_in_timings or report_spans_in_timings is true, fields are passed as the detail of measures
+    measure_detail: bool,
+    /// Only relevant if report_logs_in_timings or report_spans_in_timings is true, measure names are `|` separated
+    structured_measure_names: bool,
+    /// Only relevant if report_logs_in_console is true, repeated events are counted with console.count
+    count_duplicates: bool,
+    /// Only relevant if report_logs_in_console is true, events without a message are only reported in timings
+    require_message: bool,
+    /// Only relevant if report_logs_in_console is true, newlines within messages and fields are replaced by ` ⏎ `
+    single_line: bool,
+    /// Interval in milliseconds after which suppressed events are summarized in the console
+    flush_suppressed_every: Option<u32>,
+    /// Only relevant if report_logs_in_console is true, consecutive events of a target are grouped
+    group_by_target: bool,
+    /// Only relevant if report_logs_in_console is true, console messages start with their sequence number
+    show_sequence: bool,
+    /// Maximal level of the events displayed in the console, within the max level and target directives
+    console_max_level: Option<tracing::Level>,
+    /// Maximal level of the events and spans reported in timings, within the max level and target directives
+    timings_max_level: Option<tracing::Level>,
+    /// Only relevant if show_origin is true, the prefix removed from file paths
+    strip_path_prefix: PathPrefix,
+    /// Colors are disabled when a `NO_COLOR` global is set in the JS environment
+    respect_no_color: bool,
+    /// Only relevant if report_logs_in_console is true, how the level of events is labelled
+    level_label_style: LevelLabelStyle,
+    /// Only relevant if report_logs_in_console is true, INFO, DEBUG and TRACE events are displayed together when the browser is idle
+    batch_console: bool,
+    /// Formatter used instead of [DefaultFieldFormatter] for the fields of events and spans
+    field_formatter: Option<FieldFormatterRef>,
+    /// Whether string fields are displayed between quotes
+    quote_strings: bool,
+    /// Whether a mark is added when the layer is created, as an anchor in the timeline
+    emit_init_mark: bool,
+    /// Only relevant if report_logs_in_console is true, the time spans were busy and idle is displayed when they close
+    log_span_busy_time: bool,
+    /// Tag shown in the console messages and in the measure names of events and spans, e.g. `[checkout]`
+    message_prefix: String,
+    /// Only relevant if message_prefix is not empty, where the tag is shown in console messages
+    message_prefix_position: MessagePrefixPosition,
+    /// Prefixes of the targets whose events and spans are not reported in timings
+    timings_excluded_targets: Vec<String>,
+    /// Level from which events are displayed in the console even if report_logs_in_console is false
+    always_console_from: Option<tracing::Level>,
+    /// Levels of the events and spans which are reported, instead of those up to the max level
+    enabled_levels: Option<Vec<tracing::Level>>,
+    /// Called on every event after it is reported
+    on_event_hook: Option<EventHookRef>,
+    /// Only relevant if report_spans_in_timings is true, what the names of span measures show
+    measure_name_template: MeasureNameTemplate,
+    /// Only relevant if report_logs_in_timings is true, events are marks named after their message rather than measures
+    events_as_marks: bool,
+    /// Only relevant if report_logs_in_console is true, events are also appended to an element of the page
+    #[cfg(feature = "dom-sink")]
+    dom_sink: Option<DomSink>,
+    /// Events get the fields of their span which they don't record themselves
+    inherit_span_fields: bool,
+    /// Only relevant if report_logs_in_console is true, events show the time since their span was created
+    show_span_relative_time: bool,
+    /// Only relevant if group_spans is not Off, spans nested deeper don't open console groups
+    max_group_depth: Option<usize>,
+    /// Events are prefixed with the date and time of `Date.now()`
+    show_wall_clock: bool,
+    /// Only relevant if report_spans_in_timings is true, span measures are named after the span only
+    flamegraph_compatible: bool,
+    /// Events are also stored in this IndexedDB database
+    #[cfg(feature = "indexeddb-sink")]
+    indexeddb_sink: Option<IndexedDbSink>,
+}
+
+impl WASMLayerConfigBuilder {
+    pub fn new() -> WASMLayerConfigBuilder {
+        WASMLayerConfigBuilder::default()
+    }
+
+    /// Set whether events should appear in performance Timings
+    ///
+    /// Without the default `timings` feature, nothing is reported in timings and this has no effect.
+    pub fn set_report_logs_in_timings(
+        &mut self,
+        report_logs_in_timings: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.report_logs_in_timings = report_logs_in_timings;
+        self
+    }
+
+    /// Set whether events should appear in performance Timings, same as [WASMLayerConfigBuilder::set_report_logs_in_timings]
+    ///
+    /// Without the default `timings` feature, nothing is reported in timings and this has no effect.
+    pub fn set_report_events_in_timings(
+        &mut self,
+        report_events_in_timings: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.report_logs_in_timings = report_events_in_timings;
+        self
+    }
+
+    /// Set whether spans should appear in performance Timings
+    ///
+    /// Without the default `timings` feature, nothing is reported in timings and this has no effect.
+    pub fn set_report_spans_in_timings(
+        &mut self,
+        report_spans_in_timings: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.report_spans_in_timings = report_spans_in_timings;
+        self
+    }
+
+    /// Set the maximal level on which events should be displayed
+    pub fn set_max_level(&mut self, max_level: tracing::Level) -> &mut WASMLayerConfigBuilder {
+        self.max_level = max_level;
+        self
+    }
+
+    /// Set if and how events should be displayed in the browser console
+    ///
+    /// Without the default `console` feature, nothing is displayed in the console and this has no effect.
+    pub fn set_console_config(
+        &mut self,
+        console_config: ConsoleConfig,
+    ) -> &mut WASMLayerConfigBuilder {
+        match console_config {
+            ConsoleConfig::NoReporting => {
+                self.report_logs_in_console = false;
+                self.use_console_color = false;
+                self.use_ansi_color = false;
+                self.auto_console_color = false;
+            }
+            ConsoleConfig::ReportWithoutConsoleColor => {
+                self.report_logs_in_console = true;
+                self.use_console_color = false;
+                self.use_ansi_color = false;
+                self.auto_console_color = false;
+            }
+            ConsoleConfig::ReportWithConsoleColor => {
+                self.report_logs_in_console = true;
+                self.use_console_color = true;
+                self.use_ansi_color = false;
+                self.auto_console_color = false;
+            }
+            ConsoleConfig::ReportWithAutoColor => {
+                self.report_logs_in_console = true;
+                self.use_console_color = false;
+                self.use_ansi_color = false;
+                self.auto_console_color = true;
+            }
+            ConsoleConfig::ReportWithAnsiColor => {
+                self.report_logs_in_console = true;
+                self.use_console_color = false;
+                self.use_ansi_color = true;
+                self.auto_console_color = false;
+            }
+        }
+
+        self
+    }
+
+    /// Set the CSS styles used for the level of events in the browser console
+    pub fn set_console_colors(
+        &mut self,
+        console_colors: ConsoleColors,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.console_colors = console_colors;
+        self
+    }
+
+    /// Set whether the file:line origin of events should be displayed in the browser console
+    pub fn set_show_origin(&mut self, show_origin: bool) -> &mut WASMLayerConfigBuilder {
+        self.show_origin = show_origin;
+        self
+    }
+
+    /// Set whether the target of events should be displayed in the browser console and in timings
+    pub fn set_show_target(&mut self, show_target: bool) -> &mut WASMLayerConfigBuilder {
+        self.show_target = show_target;
+        self
+    }
+
+    /// Set the format of the messages displayed in the browser console
+    pub fn set_output_format(
+        &mut self,
+        output_format: ConsoleOutputFormat,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.output_format = output_format;
+        self
+    }
+
+    /// Set a JS function called with `(message, level)` for each event instead of logging to the browser console
+    pub fn set_sink(&mut self, sink: js_sys::Function) -> &mut WASMLayerConfigBuilder {
+        self.sink = Some(ConsoleSink::new(sink));
+        self
+    }
+
+    /// Set whether events should be prefixed with a `root > leaf` breadcrumb of their spans in the browser console
+    pub fn set_show_span_context(
+        &mut self,
+        show_span_context: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.show_span_context = show_span_context;
+        self
+    }
+
+    /// Set whether events should be followed by the fields recorded on their spans in the browser console
+    ///
+    /// This includes values recorded after a span was created, e.g. with `Span::record`.
+    pub fn set_show_span_fields(&mut self, show_span_fields: bool) -> &mut WASMLayerConfigBuilder {
+        self.show_span_fields = show_span_fields;
+        self
+    }
+
+    /// Set the browser console method used to report events of each level, [ConsoleMethodMap::by_level] by default
+    ///
+    /// Use [ConsoleMethodMap::log_only] to report every level with `console.log` as earlier versions did.
+    pub fn set_console_method_map(
+        &mut self,
+        console_methods: ConsoleMethodMap,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.console_methods = console_methods;
+        self
+    }
+
+    /// Set whether ERROR events should be reported as failed `console.assert`s, which show a JS stack trace
+    ///
+    /// This takes precedence over the method for ERROR of [set_console_method_map](WASMLayerConfigBuilder::set_console_method_map),
+    /// which is used again once this is unset.
+    pub fn set_error_as_assert(&mut self, error_as_assert: bool) -> &mut WASMLayerConfigBuilder {
+        self.error_as_assert = error_as_assert;
+        self
+    }
+
+    /// Set whether DEBUG events should be reported with `console.log` rather than the method for DEBUG of
+    /// [set_console_method_map](WASMLayerConfigBuilder::set_console_method_map), e.g. `console.debug` with
+    /// [ConsoleMethodMap::by_level]
+    ///
+    /// Chrome hides `console.debug` messages unless the "Verbose" level is selected in the console.
+    pub fn set_debug_uses_log(&mut self, debug_uses_log: bool) -> &mut WASMLayerConfigBuilder {
+        self.debug_uses_log = debug_uses_log;
+        self
+    }
+
+    /// Set a prefix for the names of performance marks, so the marks of different wasm modules on a page don't collide
+    pub fn set_mark_prefix(&mut self, mark_prefix: &str) -> &mut WASMLayerConfigBuilder {
+        self.mark_prefix = mark_prefix.to_string();
+        self
+    }
+
+    /// Set whether span fields should be passed as the `detail` object of measures rather than in their name,
+    /// so they can be read by the profiler and a `PerformanceObserver`
+    ///
+    /// The detail of event measures, event marks and span measures has the same shape:
+    ///
+    /// ```text
+    /// {
+    ///   level: "INFO",          // level of the event or span
+    ///   target: "my_crate::net",
+    ///   file: "src/net.rs",     // or null if unknown
+    ///   line: 42,               // or null if unknown
+    ///   message: "connected",   // message of the event or name of the span, or null
+    ///   fields: { retries: "2" } // recorded fields formatted with `Debug`, including the ids of a trace context
+    /// }
+    /// ```
+    pub fn set_measure_detail(&mut self, measure_detail: bool) -> &mut WASMLayerConfigBuilder {
+        self.measure_detail = measure_detail;
+        self
+    }
+
+    /// Set whether measures should be named in a stable format which is easy to split,
+    /// e.g. by tooling reading them with a `PerformanceObserver`
+    ///
+    /// Names are made of `|` separated parts:
+    ///
+    /// - spans: `span|name|module|level|fields`
+    /// - events: `event|level|target|module|fields`
+    ///
+    /// where `fields` are `;` separated `name=value` pairs, starting with the message if there is one.
+    /// The fields of spans are left empty when they are passed as the measure detail.
+    /// `\`, `|` and `;` within parts are escaped with a `\`.
+    pub fn set_structured_measure_names(
+        &mut self,
+        structured_measure_names: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.structured_measure_names = structured_measure_names;
+        self
+    }
+
+    /// Set whether repeated events should be counted with `console.count` instead of being displayed again
+    ///
+    /// Events are repeated if they have the same level, target and message, whatever their fields. Every occurrence
+    /// is counted, and the first one is also displayed in full. Only the last 1024 distinct events are remembered,
+    /// an event seen again after it was forgotten is displayed in full again.
+    pub fn set_count_duplicates(&mut self, count_duplicates: bool) -> &mut WASMLayerConfigBuilder {
+        self.count_duplicates = count_duplicates;
+        self
+    }
+
+    /// Set whether events without a message, such as metrics-style events with only fields,
+    /// should be left out of the browser console while still being reported in timings
+    pub fn set_require_message(&mut self, require_message: bool) -> &mut WASMLayerConfigBuilder {
+        self.require_message = require_message;
+        self
+    }
+
+    /// Set whether each event should be displayed on a single line, replacing newlines within messages
+    /// and fields, e.g. of pretty printed `{:#?}` values, by a visible ` ⏎ `
+    pub fn set_single_line(&mut self, single_line: bool) -> &mut WASMLayerConfigBuilder {
+        self.single_line = single_line;
+        self
+    }
+
+    /// Set an interval in milliseconds after which the events suppressed by sampling or by the
+    /// console rate limit are summarized in the console, see [WASMLayerHandle::flush_suppressed]
+    ///
+    /// The interval is checked when events occur, so nothing is displayed while the app is idle.
+    pub fn set_flush_suppressed_every(&mut self, interval_ms: u32) -> &mut WASMLayerConfigBuilder {
+        self.flush_suppressed_every = Some(interval_ms);
+        self
+    }
+
+    /// Set whether consecutive events of the same target should be wrapped in a collapsed group
+    /// of the browser console named after the target
+    ///
+    /// The group is closed when an event of another target occurs, or when a span group is opened or closed.
+    pub fn set_group_by_target(&mut self, group_by_target: bool) -> &mut WASMLayerConfigBuilder {
+        self.group_by_target = group_by_target;
+        self
+    }
+
+    /// Set whether console messages should start with `#N`, a sequence number shared by all events,
+    /// to tell apart events with the same text and timestamp
+    ///
+    /// The sequence number is not added to events reported as JSON.
+    pub fn set_show_sequence(&mut self, show_sequence: bool) -> &mut WASMLayerConfigBuilder {
+        self.show_sequence = show_sequence;
+        self
+    }
+
+    /// Set the maximal level on which events should be displayed in the browser console,
+    /// e.g. to only display INFO events while TRACE events are still reported in timings
+    ///
+    /// This further restricts the max level and the target directives, it does not extend them. Spans and events
+    /// are still enabled up to the max level, so timings keep every level.
+    pub fn set_console_max_level(
+        &mut self,
+        max_level: tracing::Level,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.console_max_level = Some(max_level);
+        self
+    }
+
+    /// Set the least severe level of the events displayed in the browser console, e.g. INFO so TRACE and DEBUG
+    /// events only go to timings
+    ///
+    /// Same as [WASMLayerConfigBuilder::set_console_max_level], levels are ordered from ERROR up to TRACE.
+    pub fn set_console_min_level(
+        &mut self,
+        min_level: tracing::Level,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.set_console_max_level(min_level)
+    }
+
+    /// Set the maximal level on which events and spans should be reported in timings, e.g. INFO so TRACE and DEBUG
+    /// events only go to the browser console, see [WASMLayerConfigBuilder::set_console_max_level]
+    ///
+    /// Without it, timings report every level enabled by the max level.
+    pub fn set_timings_max_level(
+        &mut self,
+        max_level: tracing::Level,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.timings_max_level = Some(max_level);
+        self
+    }
+
+    /// Set the least severe level of the events and spans reported in timings, e.g. INFO so TRACE and DEBUG
+    /// events only go to the browser console
+    ///
+    /// Same as [WASMLayerConfigBuilder::set_timings_max_level], levels are ordered from ERROR up to TRACE.
+    pub fn set_timings_min_level(
+        &mut self,
+        min_level: tracing::Level,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.set_timings_max_level(min_level)
+    }
+
+    /// Set a prefix to remove from the file paths of the origin of events, e.g. the absolute path of the workspace,
+    /// or [PathPrefix::Auto] to remove everything up to the last `src/`
+    pub fn set_strip_path_prefix(
+        &mut self,
+        strip_path_prefix: impl Into<PathPrefix>,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.strip_path_prefix = strip_path_prefix.into();
+        self
+    }
+
+    /// Set whether colors should be disabled when a non-empty `NO_COLOR` global is set in the JS environment,
+    /// e.g. with `globalThis.NO_COLOR = "1"` before the layer is created, following <https://no-color.org>
+    pub fn set_respect_no_color(&mut self, respect_no_color: bool) -> &mut WASMLayerConfigBuilder {
+        self.respect_no_color = respect_no_color;
+        self
+    }
+
+    /// Set how the level of events is labelled in the browser console, e.g. [LevelLabelStyle::Single]
+    /// for narrow consoles
+    pub fn set_level_label_style(
+        &mut self,
+        level_label_style: LevelLabelStyle,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.level_label_style = level_label_style;
+        self
+    }
+
+    /// Set whether INFO, DEBUG and TRACE events should be buffered and displayed together by a single
+    /// `console.log` when the browser is idle, so bursts of events don't block the main thread
+    ///
+    /// WARN and ERROR events, spans and console groups display the buffered events first, so the order is kept.
+    /// The buffer is also displayed on `beforeunload` and when the layer is dropped, or with
+    /// [WASMLayerHandle::flush_console]. Events are not batched with a sink, the JSON format, structured
+    /// console output, duplicates counting or stack traces.
+    pub fn set_batch_console(&mut self, batch_console: bool) -> &mut WASMLayerConfigBuilder {
+        self.batch_console = batch_console;
+        self
+    }
+
+    /// Set how the fields of events and spans are formatted in the console and in measure names,
+    /// instead of [DefaultFieldFormatter]
+    pub fn set_field_formatter(
+        &mut self,
+        field_formatter: impl FieldFormatter + 'static,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.field_formatter = Some(FieldFormatterRef(Arc::new(field_formatter)));
+        self
+    }
+
+    /// Set whether string fields should be displayed between quotes, like `name = "hello";`,
+    /// or as they are, like `name = hello;`
+    pub fn set_quote_strings(&mut self, quote_strings: bool) -> &mut WASMLayerConfigBuilder {
+        self.quote_strings = quote_strings;
+        self
+    }
+
+    /// Set whether a `wasm-layer-init` mark should be added when the layer is created, along with a measure
+    /// from the navigation start to it, so the timeline shows when tracing started
+    ///
+    /// The name is prefixed like the other marks. Nothing is marked when neither events nor spans are reported in
+    /// timings, or without the default `timings` feature.
+    pub fn set_emit_init_mark(&mut self, emit_init_mark: bool) -> &mut WASMLayerConfigBuilder {
+        self.emit_init_mark = emit_init_mark;
+        self
+    }
+
+    /// Set whether closing spans should be displayed in the console with the time they were busy, i.e. entered,
+    /// and idle, like `■ close "fetch" time.busy=1.2ms time.idle=30.5ms`
+    ///
+    /// Unlike the measures of spans, the busy time adds up every time the span was entered, so async spans
+    /// which are entered on each poll get their actual self time. It uses the clock of the config.
+    pub fn set_log_span_busy_time(
+        &mut self,
+        log_span_busy_time: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.log_span_busy_time = log_span_busy_time;
+        self
+    }
+
+    /// Set a tag shown in the console messages and in the measure names of events and spans, e.g. `[checkout]`,
+    /// so the logs of several wasm modules on a page can be told apart
+    pub fn set_message_prefix(&mut self, message_prefix: &str) -> &mut WASMLayerConfigBuilder {
+        self.message_prefix = message_prefix.to_string();
+        self
+    }
+
+    /// Set whether the tag of [WASMLayerConfigBuilder::set_message_prefix] is shown before or after
+    /// the level in console messages
+    pub fn set_message_prefix_position(
+        &mut self,
+        message_prefix_position: MessagePrefixPosition,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.message_prefix_position = message_prefix_position;
+        self
+    }
+
+    /// Add a prefix of the targets whose events and spans should not be reported in timings,
+    /// e.g. a chatty dependency, they are still displayed in the browser console
+    pub fn add_timings_excluded_target(&mut self, target: &str) -> &mut WASMLayerConfigBuilder {
+        self.timings_excluded_targets.push(target.to_string());
+        self
+    }
+
+    /// Set the level from which events are displayed in the browser console even when console reporting is off,
+    /// e.g. ERROR so failures are not silent when only timings are reported
+    ///
+    /// By default, nothing is displayed when console reporting is off. Spans are not displayed either way.
+    pub fn set_always_console_from(
+        &mut self,
+        level: tracing::Level,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.always_console_from = Some(level);
+        self
+    }
+
+    /// Set the exact levels of the events and spans which should be reported, e.g. ERROR and DEBUG but not WARN
+    /// and INFO, instead of every level up to the max level
+    ///
+    /// This overrides the max level, also when it is changed through a [WASMLayerHandle]. Target directives
+    /// still take precedence.
+    pub fn set_enabled_levels(
+        &mut self,
+        levels: impl IntoIterator<Item = tracing::Level>,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.enabled_levels = Some(levels.into_iter().collect());
+        self
+    }
+
+    /// Set a function called on every enabled event after it is reported in the console and in timings,
+    /// e.g. to count errors in your own metrics or forward some events to a server
+    ///
+    /// Events which are sampled out are not passed to the hook, those suppressed by the console rate limit are.
+    pub fn set_on_event_hook(
+        &mut self,
+        hook: impl Fn(&EventInfo<'_>) + Send + Sync + 'static,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.on_event_hook = Some(EventHookRef(Arc::new(hook)));
+        self
+    }
+
+    /// Set what the names of span measures show, e.g. [MeasureNameTemplate::NameOnly] for short labels
+    /// in the profiler, it has no effect with structured measure names
+    pub fn set_measure_name_template(
+        &mut self,
+        measure_name_template: MeasureNameTemplate,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.measure_name_template = measure_name_template;
+        self
+    }
+
+    /// Set whether span measures should be named with the bare name of the span, without quotes,
+    /// module path, fields or tag, so they import cleanly into flamegraph tools such as speedscope
+    ///
+    /// This takes precedence over the measure name template and structured measure names.
+    pub fn set_flamegraph_compatible(
+        &mut self,
+        flamegraph_compatible: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.flamegraph_compatible = flamegraph_compatible;
+        self
+    }
+
+    /// Set whether events should be reported as performance marks named after their message,
+    /// which the profiler shows as labeled lines, rather than as short measures
+    ///
+    /// Events without a message are named after their level and target. With [WASMLayerConfigBuilder::set_measure_detail],
+    /// their fields are passed as the detail of the mark.
+    pub fn set_events_as_marks(&mut self, events_as_marks: bool) -> &mut WASMLayerConfigBuilder {
+        self.events_as_marks = events_as_marks;
+        self
+    }
+
+    /// Set the id of an element of the page which events displayed in the browser console are also appended to,
+    /// as `<div>`s styled with the console colors, for displays without devtools
+    ///
+    /// Only the last `max_nodes` children of the element are kept. Nothing is appended where there is no such
+    /// element, e.g. in a worker.
+    #[cfg(feature = "dom-sink")]
+    pub fn set_dom_sink(
+        &mut self,
+        element_id: &str,
+        max_nodes: usize,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.dom_sink = Some(DomSink {
+            element_id: element_id.to_string(),
+            element: None,
+            max_nodes,
+        });
+        self
+    }
+
+    /// Set an element which events displayed in the browser console are also appended to, like
+    /// [set_dom_sink](WASMLayerConfigBuilder::set_dom_sink) but without looking it up by its id, e.g. an
+    /// element which isn't attached to the document yet
+    ///
+    /// Only events reported on the thread which set the element are appended to it. A config deserialized
+    /// from JSON has no element.
+    #[cfg(feature = "dom-sink")]
+    pub fn set_dom_sink_element(
+        &mut self,
+        element: web_sys::Element,
+        max_nodes: usize,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.dom_sink = Some(DomSink {
+            element_id: String::new(),
+            element: Some(DomElement::new(element)),
+            max_nodes,
+        });
+        self
+    }
+
+    /// Set the name of an IndexedDB database which events are also stored in, to export the logs of a PWA
+    /// after a crash or while offline, see `export_indexeddb_logs`
+    ///
+    /// Events enabled by the levels of the config are stored whether or not they are reported in the console, as the JSON of
+    /// [ConsoleOutputFormat::Json] with a `time` field first. They are written in batches once the current
+    /// task is done, and only the last `max_entries` are kept. Events are dropped if the database can't be opened,
+    /// e.g. where IndexedDB is unavailable.
+    #[cfg(feature = "indexeddb-sink")]
+    pub fn set_indexeddb_sink(
+        &mut self,
+        database_name: &str,
+        max_entries: usize,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.indexeddb_sink = Some(IndexedDbSink {
+            database_name: database_name.to_string(),
+            max_entries,
+        });
+        self
+    }
+
+    /// Set whether events should get the fields of their span which they don't record themselves,
+    /// e.g. the id of the request handled by the span, before their own fields
+    ///
+    /// Only the fields of the nearest span are inherited, including values recorded after it was created. With
+    /// [WASMLayerConfigBuilder::set_structured_console] or [ConsoleOutputFormat::Object], inherited fields are
+    /// passed as their formatted text, as spans don't keep the JS values of their fields.
+    pub fn set_inherit_span_fields(
+        &mut self,
+        inherit_span_fields: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.inherit_span_fields = inherit_span_fields;
+        self
+    }
+
+    /// Set whether events should be prefixed with the time since their span was created, e.g. `+12.3ms`,
+    /// according to the clock of the config
+    ///
+    /// Events outside of any span have no such prefix.
+    pub fn set_show_span_relative_time(
+        &mut self,
+        show_span_relative_time: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.show_span_relative_time = show_span_relative_time;
+        self
+    }
+
+    /// Set whether entering and exiting spans should be displayed in the browser console,
+    /// along with the time spent in the span on exit
+    pub fn set_log_span_lifecycle(
+        &mut self,
+        log_span_lifecycle: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.log_span_lifecycle = log_span_lifecycle;
+        self
+    }
+
+    /// Set the clock used for timestamps and the console rate limit, instead of `performance.now()`
+    ///
+    /// Marks and measures in performance Timings are unaffected.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) -> &mut WASMLayerConfigBuilder {
+        self.clock = Some(ClockRef(Arc::new(clock)));
+        self
+    }
+
+    /// Set the level from which events are followed by a `console.trace` of their message, to show a JS stack trace
+    /// without changing the console method used for the event itself
+    ///
+    /// Events passed to a sink are never traced.
+    pub fn set_trace_on(&mut self, level_threshold: tracing::Level) -> &mut WASMLayerConfigBuilder {
+        self.trace_on = Some(level_threshold);
+        self
+    }
+
+    /// Set whether events should be prefixed with a `performance.now()` timestamp in the browser console
+    pub fn set_show_timestamp(&mut self, show_timestamp: bool) -> &mut WASMLayerConfigBuilder {
+        self.show_timestamp = show_timestamp;
+        self
+    }
+
+    /// Set whether events should be prefixed with the UTC date and time of `Date.now()` in ISO 8601 format,
+    /// e.g. `2023-11-14T22:13:20.123Z`, to correlate exported logs with other sources
+    pub fn set_show_wall_clock(&mut self, show_wall_clock: bool) -> &mut WASMLayerConfigBuilder {
+        self.show_wall_clock = show_wall_clock;
+        self
+    }
+
+    /// Set whether entered spans should group the events within them in the browser console
+    pub fn set_group_spans(
+        &mut self,
+        group_spans: ConsoleGroupMode,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.group_spans = group_spans;
+        self
+    }
+
+    /// Set the maximal number of nested console groups opened by spans, the events of spans
+    /// nested deeper are displayed in the deepest group
+    pub fn set_max_group_depth(&mut self, max_group_depth: usize) -> &mut WASMLayerConfigBuilder {
+        self.max_group_depth = Some(max_group_depth);
+        self
+    }
+
+    /// Set the maximal level for targets starting with `target`, the longest matching prefix takes precedence
+    pub fn add_target_directive(
+        &mut self,
+        target: &str,
+        level: tracing::Level,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.target_directives
+            .push((target.to_string(), level.into()));
+        self
+    }
+
+    /// Set the maximal levels from a `RUST_LOG` style string such as `my_crate=debug,wgpu=warn,info`
+    ///
+    /// Each comma separated directive is either `target=level`, which is added as a target directive,
+    /// or a bare `level`, which sets the maximal level. The level `off` disables a target, and a bare `off`
+    /// disables every target without a directive of its own. Nothing is changed if any directive is malformed.
+    pub fn set_directives_from_str(
+        &mut self,
+        directives: &str,
+    ) -> Result<&mut WASMLayerConfigBuilder, ParseDirectivesError> {
+        let mut max_level = None;
+        let mut target_directives = Vec::new();
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            let error = || ParseDirectivesError {
+                directive: directive.to_string(),
+            };
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() {
+                        return Err(error());
+                    }
+                    let level = level.trim().parse().map_err(|_| error())?;
+                    target_directives.push((target.to_string(), level));
+                }
+                None => match directive.parse().map_err(|_| error())? {
+                    LevelFilter::OFF => target_directives.push((String::new(), LevelFilter::OFF)),
+                    level => max_level = level.into_level(),
+                },
+            }
+        }
+
+        if let Some(max_level) = max_level {
+            self.max_level = max_level;
+        }
+        self.target_directives.extend(target_directives);
+        Ok(self)
+    }
+
+    /// Set the length in bytes after which field values are truncated
+    pub fn set_max_field_len(&mut self, max_field_len: usize) -> &mut WASMLayerConfigBuilder {
+        self.max_field_len = Some(max_field_len);
+        self
+    }
+
+    /// Set the length in bytes after which messages are truncated
+    pub fn set_max_message_len(&mut self, max_message_len: usize) -> &mut WASMLayerConfigBuilder {
+        self.max_message_len = Some(max_message_len);
+        self
+    }
+
+    /// Add a field name whose value must never be displayed, such as `password`
+    pub fn add_redacted_field(&mut self, name: &str) -> &mut WASMLayerConfigBuilder {
+        self.redacted_fields.push(name.to_string());
+        self
+    }
+
+    /// Set the maximal number of events displayed in the browser console each second
+    ///
+    /// Further events are still reported in timings, and the number of suppressed messages
+    /// is displayed once the second is over.
+    pub fn set_console_rate_limit(&mut self, max_per_sec: u32) -> &mut WASMLayerConfigBuilder {
+        self.console_rate_limit = Some(max_per_sec);
+        self
+    }
+
+    /// Set that only one in `one_in` events of targets starting with `target` should be reported,
+    /// the longest matching prefix takes precedence
+    ///
+    /// Events which are sampled out are counted, see [WASMLayerHandle::sampled_out_events].
+    pub fn set_sampling(&mut self, target: &str, one_in: u32) -> &mut WASMLayerConfigBuilder {
+        self.sampling.retain(|(prefix, _)| prefix != target);
+        self.sampling.push((target.to_string(), one_in.max(1)));
+        self
+    }
+
+    /// Set whether event fields should be passed to the browser console as separate, inspectable arguments
+    ///
+    /// Errors are passed as JS `Error` objects, with their `source()` chain as `cause`.
+    pub fn set_structured_console(
+        &mut self,
+        structured_console: bool,
+    ) -> &mut WASMLayerConfigBuilder {
+        self.structured_console = structured_console;
+        self
+    }
+
+    /// Set whether the fields of spans should be shown with `console.table` when they close, instead of measured
+    /// in performance Timings
+    ///
+    /// Spans without fields when they are created are still measured.
+    pub fn set_table_for_spans(&mut self, table_for_spans: bool) -> &mut WASMLayerConfigBuilder {
+        self.table_for_spans = table_for_spans;
+        self
+    }
+
+    /// Build the WASMLayerConfig
+    pub fn build(&self) -> WASMLayerConfig {
+        WASMLayerConfig {
+            report_logs_in_timings: self.report_logs_in_timings,
+            report_spans_in_timings: self.report_spans_in_timings,
+            report_logs_in_console: self.report_logs_in_console,
+            use_console_color: self.use_console_color,
+            use_ansi_color: self.use_ansi_color,
+            auto_console_color: self.auto_console_color,
+            console_colors: self.console_colors.clone(),
+            show_origin: self.show_origin,
+            show_target: self.show_target,
+            output_format: self.output_format,
+            sink: self.sink.clone(),
+            show_span_context: self.show_span_context,
+            show_span_fields: self.show_span_fields,
+            console_methods: self.console_methods,
+            error_as_assert: self.error_as_assert,
+            debug_uses_log: self.debug_uses_log,
+            show_timestamp: self.show_timestamp,
+            group_spans: self.group_spans,
+            structured_console: self.structured_console,
+            table_for_spans: self.table_for_spans,
+            max_level: self.max_level,
+            target_directives: self.target_directives.clone(),
+            max_field_len: self.max_field_len,
+            max_message_len: self.max_message_len,
+            redacted_fields: Arc::new(self.redacted_fields.clone()),
+            sampling: self.sampling.clone(),
+            console_rate_limit: self.console_rate_limit,
+            trace_on: self.trace_on,
+            clock: self.clock.clone(),
+            log_span_lifecycle: self.log_span_lifecycle,
+            mark_prefix: self.mark_prefix.clone(),
+            measure_detail: self.measure_detail,
+            structured_measure_names: self.structured_measure_names,
+            count_duplicates: self.count_duplicates,
+            require_message: self.require_message,
+            single_line: self.single_line,
+            flush_suppressed_every: self.flush_suppressed_every,
+            group_by_target: self.group_by_target,
+            show_sequence: self.show_sequence,
+            console_max_level: self.console_max_level,
+            timings_max_level: self.timings_max_level,
+            strip_path_prefix: self.strip_path_prefix.clone(),
+            respect_no_color: self.respect_no_color,
+            level_label_style: self.level_label_style,
+            batch_console: self.batch_console,
+            field_formatter: self.field_formatter.clone(),
+            quote_strings: self.quote_strings,
+            emit_init_mark: self.emit_init_mark,
+            log_span_busy_time: self.log_span_busy_time,
+            message_prefix: self.message_prefix.clone(),
+            message_prefix_position: self.message_prefix_position,
+            timings_excluded_targets: self.timings_excluded_targets.clone(),
+            always_console_from: self.always_console_from,
+            enabled_levels: self.enabled_levels.clone(),
+            on_event_hook: self.on_event_hook.clone(),
+            measure_name_template: self.measure_name_template,
+            events_as_marks: self.events_as_marks,
+            #[cfg(feature = "dom-sink")]
+            dom_sink: self.dom_sink.clone(),
+            inherit_span_fields: self.inherit_span_fields,
+            show_span_relative_time: self.show_span_relative_time,
+            max_group_depth: self.max_group_depth,
+            show_wall_clock: self.show_wall_clock,
+            flamegraph_compatible: self.flamegraph_compatible,
+            #[cfg(feature = "indexeddb-sink")]
+            indexeddb_sink: self.indexeddb_sink.clone(),
+        }
+    }
+}
+
+/// Error returned by [WASMLayerConfigBuilder::set_directives_from_str] for a malformed directive
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseDirectivesError {
+    directive: String,
+}
+
+impl core::fmt::Display for ParseDirectivesError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid tracing directive \"{}\"", self.directive)
+    }
+}
+
+impl std::error::Error for ParseDirectivesError {}
+
+impl Default for WASMLayerConfigBuilder {
+    fn default() -> WASMLayerConfigBuilder {
+        WASMLayerConfigBuilder {
+            report_logs_in_timings: true,
+            report_spans_in_timings: true,
+            report_logs_in_console: true,
+            use_console_color: true,
+            use_ansi_color: false,
+            auto_console_color: false,
+            console_colors: ConsoleColors::default(),
+            show_origin: true,
+            show_target: false,
+            output_format: ConsoleOutputFormat::Human,
+            sink: None,
+            show_span_context: false,
+            show_span_fields: false,
+            console_methods: ConsoleMethodMap::default(),
+            error_as_assert: false,
+            debug_uses_log: false,
+            show_timestamp: false,
+            group_spans: ConsoleGroupMode::Off,
+            structured_console: false,
+            table_for_spans: false,
+            max_level: tracing::Level::TRACE,
+            target_directives: Vec::new(),
+            max_field_len: None,
+            max_message_len: None,
+            redacted_fields: Default::default(),
+            sampling: Vec::new(),
+            console_rate_limit: None,
+            trace_on: None,
+            clock: None,
+            log_span_lifecycle: false,
+            mark_prefix: String::new(),
+            measure_detail: false,
+            structured_measure_names: false,
+            count_duplicates: false,
+            require_message: false,
+            single_line: false,
+            flush_suppressed_every: None,
+            group_by_target: false,
+            show_sequence: false,
+            console_max_level: None,
+            timings_max_level: None,
+            strip_path_prefix: PathPrefix::Keep,
+            respect_no_color: true,
+            level_label_style: LevelLabelStyle::Full,
+            batch_console: false,
+            field_formatter: None,
+            quote_strings: true,
+            emit_init_mark: true,
+            log_span_busy_time: false,
+            message_prefix: String::new(),
+            message_prefix_position: MessagePrefixPosition::BeforeLevel,
+            timings_excluded_targets: Vec::new(),
+            always_console_from: None,
+            enabled_levels: None,
+            on_event_hook: None,
+            measure_name_template: MeasureNameTemplate::Full,
+            events_as_marks: false,
+            #[cfg(feature = "dom-sink")]
+            dom_sink: None,
+            inherit_span_fields: false,
+            show_span_relative_time: false,
+            max_group_depth: None,
+            show_wall_clock: false,
+            flamegraph_compatible: false,
+            #[cfg(feature = "indexeddb-sink")]
+            indexeddb_sink: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct WASMLayerConfig {
+    report_logs_in_timings: bool,
+    report_spans_in_timings: bool,
+    report_logs_in_console: bool,
+    use_console_color: bool,
+    use_ansi_color: bool,
+    auto_console_color: bool,
+    console_colors: ConsoleColors,
+    show_origin: bool,
+    show_target: bool,
+    output_format: ConsoleOutputFormat,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    sink: Option<ConsoleSink>,
+    show_span_context: bool,
+    show_span_fields: bool,
+    console_methods: ConsoleMethodMap,
+    error_as_assert: bool,
+    debug_uses_log: bool,
+    show_timestamp: bool,
+    group_spans: ConsoleGroupMode,
+    structured_console: bool,
+    table_for_spans: bool,
+    #[cfg_attr(feature = "serde", serde(with = "level_serde"))]
+    max_level: tracing::Level,
+    #[cfg_attr(feature = "serde", serde(with = "level_serde::directives"))]
+    target_directives: Vec<(String, LevelFilter)>,
+    max_field_len: Option<usize>,
+    max_message_len: Option<usize>,
+    redacted_fields: Arc<Vec<String>>,
+    sampling: Vec<(String, u32)>,
+    console_rate_limit: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(with = "level_serde::option"))]
+    trace_on: Option<tracing::Level>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    clock: Option<ClockRef>,
+    log_span_lifecycle: bool,
+    mark_prefix: String,
+    measure_detail: bool,
+    structured_measure_names: bool,
+    count_duplicates: bool,
+    require_message: bool,
+    single_line: bool,
+    flush_suppressed_every: Option<u32>,
+    group_by_target: bool,
+    show_sequence: bool,
+    #[cfg_attr(feature = "serde", serde(with = "level_serde::option"))]
+    console_max_level: Option<tracing::Level>,
+    #[cfg_attr(feature = "serde", serde(with = "level_serde::option"))]
+    timings_max_level: Option<tracing::Level>,
+    strip_path_prefix: PathPrefix,
+    respect_no_color: bool,
+    level_label_style: LevelLabelStyle,
+    batch_console: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    field_formatter: Option<FieldFormatterRef>,
+    quote_strings: bool,
+    emit_init_mark: bool,
+    log_span_busy_time: bool,
+    message_prefix: String,
+    message_prefix_position: MessagePrefixPosition,
+    timings_excluded_targets: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(with = "level_serde::option"))]
+    always_console_from: Option<tracing::Level>,
+    #[cfg_attr(feature = "serde", serde(with = "level_serde::option_set"))]
+    enabled_levels: Option<Vec<tracing::Level>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    on_event_hook: Option<EventHookRef>,
+    measure_name_template: MeasureNameTemplate,
+    events_as_marks: bool,
+    #[cfg(feature = "dom-sink")]
+    dom_sink: Option<DomSink>,
+    inherit_span_fields: bool,
+    show_span_relative_time: bool,
+    max_group_depth: Option<usize>,
+    show_wall_clock: bool,
+    flamegraph_compatible: bool,
+    #[cfg(feature = "indexeddb-sink")]
+    indexeddb_sink: Option<IndexedDbSink>,
+}
+
+impl WASMLayerConfig {
+    /// Current time in milliseconds according to the clock of the config
+    fn now_ms(&self) -> f64 {
+        match &self.clock {
+            Some(clock) => clock.0.now_ms(),
+            None => PerformanceClock.now_ms(),
+        }
+    }
+
+    /// Whether the level of an event or span is enabled by the target directives or `max_level`,
+    /// further restricted by the max level of the console or of timings
+    fn level_enabled(
+        &self,
+        metadata: &tracing::Metadata<'_>,
+        max_level: tracing::Level,
+        output_max_level: Option<tracing::Level>,
+    ) -> bool {
+        let level = metadata.level();
+        let enabled = match (
+            self.target_max_level(metadata.target()),
+            &self.enabled_levels,
+        ) {
+            (Some(max_level), _) => *level <= max_level,
+            (None, Some(enabled_levels)) => enabled_levels.contains(level),
+            (None, None) => *level <= max_level,
+        };
+        enabled
+            && match output_max_level {
+                Some(output_max_level) => level <= &output_max_level,
+                None => true,
+            }
+    }
+
+    /// Maximal level of the longest target directive matching `target`
+    fn target_max_level(&self, target: &str) -> Option<LevelFilter> {
+        self.target_directives
+            .iter()
+            .filter(|(prefix, _)| target.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+    }
+
+    /// Console method for `level`, with the overrides of error_as_assert and debug_uses_log
+    #[cfg(feature = "console")]
+    fn console_method(&self, level: &tracing::Level) -> ConsoleMethod {
+        match *level {
+            tracing::Level::ERROR if self.error_as_assert => ConsoleMethod::Assert,
+            tracing::Level::DEBUG if self.debug_uses_log => ConsoleMethod::Log,
+            _ => self.console_methods.for_level(level),
+        }
+    }
+
+    /// Whether the `wasm-layer-init` mark should be added, only when something else is reported in timings
+    #[cfg(feature = "timings")]
+    fn marks_init(&self) -> bool {
+        self.emit_init_mark && (self.report_logs_in_timings || self.report_spans_in_timings)
+    }
+
+    /// Whether the fields of spans are recorded, which is skipped when measure names only show the span name
+    /// and nothing else displays or inherits the fields
+    fn records_span_fields(&self) -> bool {
+        #[cfg(feature = "dom-sink")]
+        let dom_sink = self.dom_sink.is_some();
+        #[cfg(not(feature = "dom-sink"))]
+        let dom_sink = false;
+        self.measure_name_template != MeasureNameTemplate::NameOnly
+            || self.measure_detail
+            || self.structured_measure_names
+            || self.report_logs_in_console
+            || self.always_console_from.is_some()
+            || self.inherit_span_fields
+            || dom_sink
+    }
+
+    /// Index of the longest sampling prefix matching `target`
+    fn sampling_index(&self, target: &str) -> Option<usize> {
+        self.sampling
+            .iter()
+            .enumerate()
+            .filter(|(_, (prefix, _))| target.starts_with(prefix.as_str()))
+            .max_by_key(|(_, (prefix, _))| prefix.len())
+            .map(|(idx, _)| idx)
+    }
+}
+
+impl core::default::Default for WASMLayerConfig {
+    fn default() -> Self {
+        WASMLayerConfig {
+            report_logs_in_timings: true,
+            report_spans_in_timings: true,
+            report_logs_in_console: true,
+            use_console_color: true,
+            use_ansi_color: false,
+            auto_console_color: false,
+            console_colors: ConsoleColors::default(),
+            show_origin: true,
+            show_target: false,
+            output_format: ConsoleOutputFormat::Human,
+            sink: None,
+            show_span_context: false,
+            show_span_fields: false,
+            console_methods: ConsoleMethodMap::default(),
+            error_as_assert: false,
+            debug_uses_log: false,
+            show_timestamp: false,
+            group_spans: ConsoleGroupMode::Off,
+            structured_console: false,
+            table_for_spans: false,
+            max_level: tracing::Level::TRACE,
+            target_directives: Vec::new(),
+            max_field_len: None,
+            max_message_len: None,
+            redacted_fields: Default::default(),
+            sampling: Vec::new(),
+            console_rate_limit: None,
+            trace_on: None,
+            clock: None,
+            log_span_lifecycle: false,
+            mark_prefix: String::new(),
+            measure_detail: false,
+            structured_measure_names: false,
+            count_duplicates: false,
+            require_message: false,
+            single_line: false,
+            flush_suppressed_every: None,
+            group_by_target: false,
+            show_sequence: false,
+            console_max_level: None,
+            timings_max_level: None,
+            strip_path_prefix: PathPrefix::Keep,
+            respect_no_color: true,
+            level_label_style: LevelLabelStyle::Full,
+            batch_console: false,
+            field_formatter: None,
+            quote_strings: true,
+            emit_init_mark: true,
+            log_span_busy_time: false,
+            message_prefix: String::new(),
+            message_prefix_position: MessagePrefixPosition::BeforeLevel,
+            timings_excluded_targets: Vec::new(),
+            always_console_from: None,
+            enabled_levels: None,
+            on_event_hook: None,
+            measure_name_template: MeasureNameTemplate::Full,
+            events_as_marks: false,
+            #[cfg(feature = "dom-sink")]
+            dom_sink: None,
+            inherit_span_fields: false,
+            show_span_relative_time: false,
+            max_group_depth: None,
+            show_wall_clock: false,
+            flamegraph_compatible: false,
+            #[cfg(feature = "indexeddb-sink")]
+            indexeddb_sink: None,
+        }
+    }
+}
+
+/// Implements [tracing_subscriber::layer::Layer] which uses [wasm_bindgen] for marking and measuring with `window.performance`
+pub struct WASMLayer {
+    config: WASMLayerConfig,
+    /// Key of the data this layer stores in the extensions of spans
+    id: LayerId,
+    shared: Arc<SharedState>,
+    #[cfg(feature = "timings")]
+    timings: Timings,
+    #[cfg(feature = "console")]
+    rate_limit_window: Mutex<RateLimitWindow>,
+    #[cfg(feature = "console")]
+    /// Keys of the events displayed so far, when duplicates are counted
+    seen_events: Mutex<SeenEvents>,
+    /// Time of the last automatic flush of the suppressed events
+    last_suppressed_flush: Mutex<Option<f64>>,
+    #[cfg(feature = "console")]
+    /// Target of the console group opened for the last events, when grouping by target
+    open_target_group: Mutex<Option<&'static str>>,
+    #[cfg(feature = "console")]
+    /// Number of events displayed in the console so far, when showing their sequence number
+    event_sequence: AtomicUsize,
+    #[cfg(feature = "console")]
+    /// Number of console groups opened by spans which are still open
+    span_group_depth: AtomicUsize,
+}
+
+#[cfg(feature = "console")]
+/// Console messages displayed and suppressed in the current second of the rate limit
+#[derive(Default)]
+struct RateLimitWindow {
+    start: f64,
+    displayed: u32,
+    suppressed: usize,
+}
+
+/// Number of distinct events remembered when duplicates are counted
+#[cfg(feature = "console")]
+const MAX_SEEN_EVENTS: usize = 1024;
+
+#[cfg(feature = "console")]
+/// Keys of the events displayed so far when duplicates are counted, the least recently seen are forgotten
+/// beyond [MAX_SEEN_EVENTS]
+#[derive(Default)]
+struct SeenEvents {
+    /// The tick each key was last seen at
+    last_seen: HashMap<String, u64>,
+    tick: u64,
+}
+
+#[cfg(feature = "console")]
+impl SeenEvents {
+    /// Whether the key is seen for the first time, or again after it was forgotten
+    fn insert(&mut self, key: &str) -> bool {
+        self.tick += 1;
+        if let Some(last_seen) = self.last_seen.get_mut(key) {
+            *last_seen = self.tick;
+            return false;
+        }
+        if self.last_seen.len() >= MAX_SEEN_EVENTS {
+            let least_recent = self
+                .last_seen
+                .iter()
+                .min_by_key(|(_, last_seen)| **last_seen)
+                .map(|(key, _)| key.clone());
+            if let Some(least_recent) = least_recent {
+                self.last_seen.remove(&least_recent);
+            }
+        }
+        self.last_seen.insert(key.to_string(), self.tick);
+        true
+    }
+}
+
+impl WASMLayer {
+    pub fn new(mut config: WASMLayerConfig) -> Self {
+        if config.auto_console_color {
+            config.use_console_color = detect_browser_console();
+        }
+        if config.respect_no_color && detect_no_color() {
+            config.use_console_color = false;
+            config.use_ansi_color = false;
+        }
+        let layer = WASMLayer {
+            id: LayerId::next(),
+            #[cfg(feature = "timings")]
+            timings: Timings::new(),
+            #[cfg(feature = "console")]
+            rate_limit_window: Mutex::new(RateLimitWindow::default()),
+            #[cfg(feature = "console")]
+            seen_events: Mutex::new(SeenEvents::default()),
+            last_suppressed_flush: Mutex::new(None),
+            #[cfg(feature = "console")]
+            open_target_group: Mutex::new(None),
+            #[cfg(feature = "console")]
+            event_sequence: AtomicUsize::new(0),
+            #[cfg(feature = "console")]
+            span_group_depth: AtomicUsize::new(0),
+            shared: Arc::new(SharedState {
+                max_level: AtomicU8::new(level_to_u8(config.max_level)),
+                console_enabled: AtomicBool::new(true),
+                sampled_seen: config
+                    .sampling
+                    .iter()
+                    .map(|_| AtomicUsize::new(0))
+                    .collect(),
+                sampled_out: AtomicUsize::new(0),
+                open_spans: AtomicUsize::new(0),
+                #[cfg(feature = "timings")]
+                last_event_id: AtomicUsize::new(0),
+                suppressed: Mutex::new(BTreeMap::new()),
+                trace_context: Mutex::new(Vec::new()),
+                #[cfg(feature = "console")]
+                console_batch: Mutex::new(ConsoleBatch::default()),
+            }),
+            config,
+        };
+        #[cfg(feature = "console")]
+        if layer.config.batch_console {
+            flush_before_unload(&layer.shared);
+        }
+        #[cfg(feature = "timings")]
+        if layer.config.marks_init() && layer.timings.available {
+            layer.mark_init();
+        }
+        layer
+    }
+
+    /// Whether an event of `target` should be reported according to the sampling of the config
+    fn sample(&self, target: &'static str) -> bool {
+        let idx = match self.config.sampling_index(target) {
+            Some(idx) => idx,
+            None => return true,
+        };
+        let one_in = self.config.sampling[idx].1 as usize;
+        let seen = self.shared.sampled_seen[idx].fetch_add(1, Ordering::Relaxed);
+        if seen.checked_rem(one_in) == Some(0) {
+            true
+        } else {
+            self.shared.sampled_out.fetch_add(1, Ordering::Relaxed);
+            self.shared.count_suppressed(target);
+            false
+        }
+    }
+
+    /// Flush the suppressed events if the interval of the config has passed since the last flush
+    fn auto_flush_suppressed(&self) {
+        let interval = match self.config.flush_suppressed_every {
+            Some(interval) => f64::from(interval),
+            None => return,
+        };
+        let now = self.config.now_ms();
+        let mut last_flush = self
+            .last_suppressed_flush
+            .lock()
+            .expect("last suppressed flush");
+        match *last_flush {
+            Some(last) if now - last < interval => {}
+            Some(_) => {
+                *last_flush = Some(now);
+                self.shared.flush_suppressed();
+            }
+            None => *last_flush = Some(now),
+        }
+    }
+
+    /// Whether the level of an event or span is enabled by the target directives or the current max level,
+    /// further restricted by the max level of the console or of timings
+    fn level_enabled(
+        &self,
+        metadata: &tracing::Metadata<'_>,
+        output_max_level: Option<tracing::Level>,
+    ) -> bool {
+        self.config
+            .level_enabled(metadata, self.shared.max_level(), output_max_level)
+    }
+
+    fn console_enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        self.shared.console_enabled.load(Ordering::Relaxed)
+            && self.level_enabled(metadata, self.config.console_max_level)
+    }
+
+    fn timings_enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        self.level_enabled(metadata, self.config.timings_max_level)
+            && !self
+                .config
+                .timings_excluded_targets
+                .iter()
+                .any(|prefix| metadata.target().starts_with(prefix.as_str()))
+    }
+
+    /// Get a handle to change the configuration of this layer while it is in use
+    pub fn handle(&self) -> WASMLayerHandle {
+        WASMLayerHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Report an event in the console and in timings, and pass it to the hook of the config
+    #[cfg_attr(not(feature = "console"), allow(unused_variables))]
+    fn report_event<S: Subscriber + for<'a> LookupSpan<'a>>(
+        &self,
+        event: &tracing::Event<'_>,
+        recorder: &mut StringRecorder,
+        ctx: &Context<'_, S>,
+    ) {
+        // the JS values of the fields are recorded in the same pass, when the console displays them
+        #[cfg(feature = "console")]
+        let mut js_recorder = if self.console_shows_js_values() {
+            Some(JsRecorder::new(&self.config))
+        } else {
+            None
+        };
+        #[cfg(feature = "console")]
+        match &mut js_recorder {
+            Some(js_recorder) => event.record(&mut TeeRecorder {
+                text: recorder,
+                js: js_recorder,
+            }),
+            None => event.record(recorder),
+        }
+        #[cfg(not(feature = "console"))]
+        event.record(recorder);
+        let trace_context = self
+            .shared
+            .trace_context
+            .lock()
+            .expect("trace context")
+            .clone();
+        recorder.add_missing_fields(&trace_context);
+        if self.config.inherit_span_fields {
+            if let Some(span_ref) = ctx.event_span(event) {
+                if let Some(span_recorder) = self.id.get::<StringRecorder>(&span_ref.extensions()) {
+                    recorder.inherit_fields(&span_recorder.fields);
+                }
+            }
+        }
+        let meta = event.metadata();
+        #[cfg(feature = "indexeddb-sink")]
+        self.store_event_in_indexeddb(meta, recorder);
+        #[cfg(feature = "console")]
+        {
+            let has_message = matches!(&recorder.message, Some(message) if !message.is_empty());
+            let always_console =
+                matches!(self.config.always_console_from, Some(from) if *meta.level() <= from);
+            if (self.config.report_logs_in_console || always_console) && self.console_enabled(meta)
+            {
+                match (recorder.group, &self.config.sink) {
+                    // not rate limited, so every opened group is closed
+                    (Some(command), None) => self.apply_group_command(
+                        command,
+                        recorder.message.as_deref(),
+                        meta.target(),
+                    ),
+                    _ if (has_message || !self.config.require_message)
+                        && self.within_rate_limit(meta.target()) =>
+                    {
+                        self.log_event(event, recorder, js_recorder.as_ref(), &trace_context, ctx);
+                        #[cfg(feature = "dom-sink")]
+                        self.append_event_to_dom(event, recorder, ctx);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        #[cfg(feature = "timings")]
+        if self.config.report_logs_in_timings
+            && self.timings_enabled(meta)
+            && self.timings.available()
+        {
+            if self.config.events_as_marks {
+                self.mark_event(meta, recorder);
+            } else {
+                self.report_event_timing(meta, recorder);
+            }
+        }
+        if let Some(hook) = &self.config.on_event_hook {
+            (hook.0)(&EventInfo {
+                level: *meta.level(),
+                target: meta.target(),
+                message: recorder.message.as_deref(),
+                fields: &recorder.fields,
+            });
+        }
+    }
+}
+
+#[cfg(feature = "console")]
+impl WASMLayer {
+    /// Whether an event can be displayed in the console according to the rate limit of the config
+    fn within_rate_limit(&self, target: &'static str) -> bool {
+        let max_per_sec = match self.config.console_rate_limit {
+            Some(max_per_sec) => max_per_sec,
+            None => return true,
+        };
+        let now = self.config.now_ms();
+        let mut window = self.rate_limit_window.lock().expect("rate limit window");
+        if now - window.start >= 1000.0 {
+            if window.suppressed > 0 {
+                let message = format!(
+                    "tracing-wasm: {} messages suppressed by the console rate limit",
+                    window.suppressed
+                );
+                match &self.config.sink {
+                    Some(sink) => sink.call(message, &tracing::Level::WARN),
+                    None => warn1(message),
+                }
+            }
+            *window = RateLimitWindow {
+                start: now,
+                ..RateLimitWindow::default()
+            };
+        }
+        if window.displayed < max_per_sec {
+            window.displayed += 1;
+            true
+        } else {
+            window.suppressed += 1;
+            self.shared.count_suppressed(target);
+            false
+        }
+    }
+
+    /// Display the batched console messages before a message which isn't batched, so they stay in order
+    fn flush_console_batch(&self) {
+        if self.config.batch_console {
+            self.shared.flush_console_batch();
+        }
+    }
+
+    /// Open a console group for the target of an event, closing the group of the previous target
+    fn enter_target_group(&self, target: &'static str) {
+        let mut open_group = self.open_target_group.lock().expect("open target group");
+        if *open_group != Some(target) {
+            self.flush_console_batch();
+            if open_group.is_some() {
+                group_end();
+            }
+            group_collapsed(target);
+            *open_group = Some(target);
+        }
+    }
+
+    /// Close the console group of the last target, so span groups are not nested in it
+    fn close_target_group(&self) {
+        if self
+            .open_target_group
+            .lock()
+            .expect("open target group")
+            .take()
+            .is_some()
+        {
+            self.flush_console_batch();
+            group_end();
+        }
+    }
+
+    /// Open or close a console group for an event with a [GROUP_FIELD], labelled with its message
+    /// or else its target
+    fn apply_group_command(&self, command: GroupCommand, message: Option<&str>, target: &str) {
+        self.close_target_group();
+        self.flush_console_batch();
+        match command {
+            GroupCommand::Open => group(message.unwrap_or(target)),
+            GroupCommand::Close => group_end(),
+        }
+    }
+
+    /// Whether an event with this key is displayed for the first time
+    fn first_occurrence(&self, key: &str) -> bool {
+        self.seen_events.lock().expect("seen events").insert(key)
+    }
+
+    /// Whether events are displayed in the console with the JS values of their fields, rather than only as text
+    fn console_shows_js_values(&self) -> bool {
+        cfg!(target_arch = "wasm32")
+            && self.config.sink.is_none()
+            && (self.config.structured_console
+                || self.config.output_format == ConsoleOutputFormat::Object)
+    }
+
+    /// Display a span entering or exiting with the console method and style of DEBUG events
+    fn log_span_lifecycle(&self, arrow: &str, rest: &str) {
+        self.flush_console_batch();
+        let level = &tracing::Level::DEBUG;
+        if let Some(sink) = &self.config.sink {
+            sink.call(format!("{}{}", arrow, rest), level);
+        } else if self.config.use_console_color {
+            self.config.console_method(level).log3(
+                &format!("%c{}%c{}", arrow, rest),
+                self.config.console_colors.for_level(level),
+                &self.config.console_colors.message_style,
+            );
+        } else if self.config.use_ansi_color {
+            self.config.console_method(level).log1(format!(
+                "{}{}{}{}",
+                ansi_color(level),
+                arrow,
+                ANSI_RESET,
+                rest
+            ));
+        } else {
+            self.config
+                .console_method(level)
+                .log1(format!("{}{}", arrow, rest));
+        }
+    }
+
+    /// Display an event in the browser console, or pass it to the sink of the config
+    fn log_event<S: Subscriber + for<'a> LookupSpan<'a>>(
+        &self,
+        event: &tracing::Event<'_>,
+        recorder: &StringRecorder,
+        js_recorder: Option<&JsRecorder<'_>>,
+        trace_context: &[(&'static str, String)],
+        ctx: &Context<'_, S>,
+    ) {
+        let meta = event.metadata();
+        let level = meta.level();
+        if self.config.group_by_target && self.config.sink.is_none() {
+            self.enter_target_group(meta.target());
+        }
+        let sequence = if self.config.show_sequence {
+            Some(self.event_sequence.fetch_add(1, Ordering::Relaxed) + 1)
+        } else {
+            None
+        };
+        let traced = self.config.sink.is_none()
+            && matches!(self.config.trace_on, Some(trace_on) if *level <= trace_on);
+        let duplicate_key = if self.config.count_duplicates && self.config.sink.is_none() {
+            Some(match &recorder.message {
+                Some(message) => format!("{} {} {}", level, meta.target(), message),
+                None => format!("{} {}", level, meta.target()),
+            })
+        } else {
+            None
+        };
+        let batched = self.config.batch_console
+            && self.config.sink.is_none()
+            && self.config.output_format != ConsoleOutputFormat::Json
+            && self.config.output_format != ConsoleOutputFormat::Fmt
+            && !((self.config.structured_console
+                || self.config.output_format == ConsoleOutputFormat::Object)
+                && cfg!(target_arch = "wasm32"))
+            && duplicate_key.is_none()
+            && !traced
+            && *level > tracing::Level::WARN;
+        if !batched {
+            self.flush_console_batch();
+        }
+        match duplicate_key {
+            Some(key) if !self.first_occurrence(&key) => count(&key),
+            _ => {
+                if matches!(
+                    self.config.output_format,
+                    ConsoleOutputFormat::Json | ConsoleOutputFormat::Fmt
+                ) {
+                    let message = match self.config.output_format {
+                        ConsoleOutputFormat::Fmt => {
+                            fmt_event(meta, recorder, self.id, ctx.event_span(event))
+                        }
+                        _ => json_event(meta, recorder),
+                    };
+                    match &self.config.sink {
+                        Some(sink) => sink.call(message, level),
+                        None if traced => {
+                            self.config.console_method(level).log1(message.clone());
+                            trace1(message);
+                        }
+                        None => self.config.console_method(level).log1(message),
+                    }
+                } else {
+                    let span = SpanContext::new(&self.config, self.id, ctx.event_span(event));
+
+                    if let Some(sink) = &self.config.sink {
+                        let formatted = format_recorded_event(&self.config, meta, &span, recorder)
+                            .with_sequence(sequence);
+                        sink.call(formatted.plain(), level);
+                    } else if let (true, Some(js_recorder)) =
+                        (self.config.structured_console, js_recorder)
+                    {
+                        let header = EventMessage {
+                            tag: config_tag_after_level(&self.config),
+                            span: &span,
+                            body: js_recorder,
+                        };
+                        // the message is the format string, so its own `%` must not be read as directives
+                        let message = plain_message(
+                            &sequence_prefix(sequence, event_prefix(&self.config, &span)),
+                            self.config.level_label_style.label(level),
+                            event_origin(&self.config, meta).as_deref(),
+                            &header,
+                        )
+                        .replace('%', "%%");
+                        let args = js_sys::Array::of1(&JsValue::from(message));
+                        for (name, value) in js_fields(recorder, js_recorder, trace_context) {
+                            args.push(&JsValue::from(format!("{} =", name)));
+                            args.push(&value);
+                        }
+                        self.config.console_method(level).log_variadic(&args);
+                    } else if let (ConsoleOutputFormat::Object, Some(js_recorder)) =
+                        (self.config.output_format, js_recorder)
+                    {
+                        let fields = js_sys::Object::new();
+                        for (name, value) in js_fields(recorder, js_recorder, trace_context) {
+                            let _ = js_sys::Reflect::set(&fields, &JsValue::from_str(name), &value);
+                        }
+                        let header = EventMessage {
+                            tag: config_tag_after_level(&self.config),
+                            span: &span,
+                            body: js_recorder,
+                        };
+                        // the message is the format string, so its own `%` must not be read as directives
+                        let message = plain_message(
+                            &sequence_prefix(sequence, event_prefix(&self.config, &span)),
+                            self.config.level_label_style.label(level),
+                            event_origin(&self.config, meta).as_deref(),
+                            &header,
+                        )
+                        .replace('%', "%%");
+                        let args =
+                            js_sys::Array::of2(&JsValue::from(format!("{} %o", message)), &fields);
+                        self.config.console_method(level).log_variadic(&args);
+                    } else {
+                        let formatted = format_recorded_event(&self.config, meta, &span, recorder)
+                            .with_sequence(sequence);
+                        let method = self.config.console_method(level);
+                        if self.config.use_console_color {
+                            let styled = formatted.styled();
+                            match formatted.styles()[..] {
+                                ref styles if batched => {
+                                    self.shared.push_console_batch(&styled, styles)
+                                }
+                                [level_style, origin_style, message_style] => {
+                                    method.log4(styled, level_style, origin_style, message_style)
+                                }
+                                [level_style, message_style] => {
+                                    method.log3(&styled, level_style, message_style)
+                                }
+                                _ => unreachable!("events are styled with two or three styles"),
+                            }
+                        } else {
+                            let message = if self.config.use_ansi_color {
+                                formatted.ansi()
+                            } else {
+                                formatted.plain()
+                            };
+                            if batched {
+                                self.shared.push_console_batch(&message, &[]);
+                            } else {
+                                method.log1(message);
+                            }
+                        }
+                    }
+                    if traced {
+                        trace1(
+                            format_recorded_event(&self.config, meta, &span, recorder)
+                                .with_sequence(sequence)
+                                .plain(),
+                        );
+                    }
+                }
+                if let Some(key) = duplicate_key {
+                    count(&key);
+                }
+            }
+        }
+    }
+
+    /// Display a span being entered in the console, by its lifecycle or by opening a console group
+    fn log_span_enter<S: for<'a> LookupSpan<'a>>(&self, span_ref: &SpanRef<'_, S>) {
+        if let Some(busy_time) = self.id.get_mut::<BusyTime>(&mut span_ref.extensions_mut()) {
+            if busy_time.entered == 0 {
+                busy_time.entered_at = Some(self.config.now_ms());
+            }
+            busy_time.entered += 1;
+        }
+        if self.config.log_span_lifecycle {
+            self.log_span_lifecycle("→ enter", &format!(" \"{}\"", span_ref.name()));
+            self.id.insert(
+                &mut span_ref.extensions_mut(),
+                EnteredAt(self.config.now_ms()),
+            );
+        }
+        let within_max_depth = match self.config.max_group_depth {
+            Some(max_group_depth) => {
+                self.span_group_depth.load(Ordering::Relaxed) < max_group_depth
+            }
+            None => true,
+        };
+        if self.config.group_spans != ConsoleGroupMode::Off && within_max_depth {
+            self.span_group_depth.fetch_add(1, Ordering::Relaxed);
+            self.close_target_group();
+            self.flush_console_batch();
+            self.config.group_spans.open(span_ref.name());
+            // count the groups opened by this span so they are closed on exit even if the span is re-entered
+            let mut extensions = span_ref.extensions_mut();
+            match self.id.get_mut::<OpenConsoleGroups>(&mut extensions) {
+                Some(open_groups) => open_groups.0 += 1,
+                None => self.id.insert(&mut extensions, OpenConsoleGroups(1)),
+            }
+        }
+    }
+
+    /// Display a span closing with the time it was busy and idle, if it was tracked since its creation
+    fn log_span_close<S: for<'a> LookupSpan<'a>>(&self, span_ref: &SpanRef<'_, S>) {
+        if self
+            .id
+            .remove::<TabledSpan>(&mut span_ref.extensions_mut())
+            .is_some()
+        {
+            if let Some(debug_record) = self.id.get::<StringRecorder>(&span_ref.extensions()) {
+                self.flush_console_batch();
+                debug_record.log_table();
+            }
+        }
+        let busy_time = match self.id.remove::<BusyTime>(&mut span_ref.extensions_mut()) {
+            Some(busy_time) => busy_time,
+            None => return,
+        };
+        let total = self.config.now_ms() - busy_time.created_at;
+        self.log_span_lifecycle(
+            "■ close",
+            &format!(
+                " \"{}\" time.busy={:.1}ms time.idle={:.1}ms",
+                span_ref.name(),
+                busy_time.busy,
+                total - busy_time.busy
+            ),
+        );
+    }
+
+    /// Close the console group of a span being exited and display it in the console
+    fn log_span_exit<S: for<'a> LookupSpan<'a>>(&self, span_ref: &SpanRef<'_, S>) {
+        if let Some(busy_time) = self.id.get_mut::<BusyTime>(&mut span_ref.extensions_mut()) {
+            busy_time.entered = busy_time.entered.saturating_sub(1);
+            if busy_time.entered == 0 {
+                if let Some(entered_at) = busy_time.entered_at.take() {
+                    busy_time.busy += self.config.now_ms() - entered_at;
+                }
+            }
+        }
+        if let Some(open_groups) = self
+            .id
+            .get_mut::<OpenConsoleGroups>(&mut span_ref.extensions_mut())
+        {
+            if open_groups.0 > 0 {
+                open_groups.0 -= 1;
+                self.span_group_depth.fetch_sub(1, Ordering::Relaxed);
+                self.close_target_group();
+                self.flush_console_batch();
+                group_end();
+            }
+        }
+        if !self.config.report_logs_in_console || !self.console_enabled(span_ref.metadata()) {
+            return;
+        }
+        if self.config.log_span_lifecycle {
+            let duration = match self.id.get::<EnteredAt>(&span_ref.extensions()) {
+                Some(entered_at) => format!(" ({:.1}ms)", self.config.now_ms() - entered_at.0),
+                None => String::new(),
+            };
+            self.log_span_lifecycle("← exit", &format!(" \"{}\"{}", span_ref.name(), duration));
+        }
+    }
+}
+
+/// Check for the globals of a browser page or worker, whose consoles support `%c` styling
+#[cfg(target_arch = "wasm32")]
+fn detect_browser_console() -> bool {
+    ["document", "importScripts"].iter().any(|name| {
+        js_sys::Reflect::has(&js_sys::global(), &JsValue::from_str(name)).unwrap_or(false)
+    })
+}
+
+/// There is no browser outside of wasm
+#[cfg(not(target_arch = "wasm32"))]
+fn detect_browser_console() -> bool {
+    false
+}
+
+/// Check for a non-empty `NO_COLOR` global
+#[cfg(target_arch = "wasm32")]
+fn detect_no_color() -> bool {
+    match js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("NO_COLOR")) {
+        Ok(value) if value.is_undefined() || value.is_null() => false,
+        Ok(value) => !matches!(value.as_string(), Some(value) if value.is_empty()),
+        Err(_) => false,
+    }
+}
+
+/// There are no JS globals outside of wasm
+#[cfg(not(target_arch = "wasm32"))]
+fn detect_no_color() -> bool {
+    false
+}
+
+/// Configuration of a [WASMLayer] which can be changed through a [WASMLayerHandle]
+struct SharedState {
+    max_level: AtomicU8,
+    /// Events and spans are displayed in the console, if the config reports them there
+    console_enabled: AtomicBool,
+    /// Events seen for each of the sampled targets of the config
+    sampled_seen: Vec<AtomicUsize>,
+    sampled_out: AtomicUsize,
+    /// Spans seen by the layer which are not closed yet
+    open_spans: AtomicUsize,
+    #[cfg(feature = "timings")]
+    /// Number in the name of the mark of the next event
+    last_event_id: AtomicUsize,
+    /// Events suppressed by sampling or by the console rate limit since the last flush, by target
+    suppressed: Mutex<BTreeMap<&'static str, usize>>,
+    /// `trace_id` and `span_id` fields added to the events which don't have their own
+    trace_context: Mutex<Vec<(&'static str, String)>>,
+    #[cfg(feature = "console")]
+    /// Console messages waiting to be displayed, when batching the console
+    console_batch: Mutex<ConsoleBatch>,
+}
+
+impl SharedState {
+    fn max_level(&self) -> tracing::Level {
+        level_from_u8(self.max_level.load(Ordering::Relaxed))
+    }
+
+    fn count_suppressed(&self, target: &'static str) {
+        *self
+            .suppressed
+            .lock()
+            .expect("suppressed events")
+            .entry(target)
+            .or_insert(0) += 1;
+    }
+
+    /// Take the suppressed events counts, and summarize them in the console if there are any
+    fn flush_suppressed(&self) -> Vec<(&'static str, usize)> {
+        let counts: Vec<_> =
+            core::mem::take(&mut *self.suppressed.lock().expect("suppressed events"))
+                .into_iter()
+                .collect();
+        #[cfg(feature = "console")]
+        if !counts.is_empty() {
+            let summary = counts
+                .iter()
+                .map(|(target, count)| format!("{}: {}", target, count))
+                .collect::<Vec<_>>()
+                .join(", ");
+            info1(format!(
+                "tracing-wasm: suppressed events by target: {}",
+                summary
+            ));
+        }
+        counts
+    }
+}
+
+fn level_to_u8(level: tracing::Level) -> u8 {
+    match level {
+        tracing::Level::ERROR => 1,
+        tracing::Level::WARN => 2,
+        tracing::Level::INFO => 3,
+        tracing::Level::DEBUG => 4,
+        tracing::Level::TRACE => 5,
+    }
+}
+
+fn level_from_u8(level: u8) -> tracing::Level {
+    match level {
+        1 => tracing::Level::ERROR,
+        2 => tracing::Level::WARN,
+        3 => tracing::Level::INFO,
+        4 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    }
+}
+
+/// Handle to change the configuration of a [WASMLayer] while it is in use
+#[derive(Clone)]
+pub struct WASMLayerHandle {
+    shared: Arc<SharedState>,
+}
+
+impl WASMLayerHandle {
+    /// Set the maximal level on which events should be displayed, target directives still take precedence
+    pub fn set_max_level(&self, max_level: tracing::Level) {
+        self.shared
+            .max_level
+            .store(level_to_u8(max_level), Ordering::Relaxed);
+        // callsites cache whether they are enabled, so they need to be asked again
+        tracing::callsite::rebuild_interest_cache();
+    }
+
+    /// Get the current maximal level on which events are displayed
+    pub fn max_level(&self) -> tracing::Level {
+        self.shared.max_level()
+    }
+
+    /// Set whether events and spans should be displayed in the browser console, if the config reports them there,
+    /// timings are unaffected
+    pub fn set_console_enabled(&self, enabled: bool) {
+        self.shared
+            .console_enabled
+            .store(enabled, Ordering::Relaxed);
+        tracing::callsite::rebuild_interest_cache();
+    }
+
+    /// Get the number of events which were not reported because of sampling
+    pub fn sampled_out_events(&self) -> usize {
+        self.shared.sampled_out.load(Ordering::Relaxed)
+    }
+
+    /// Restart the numbering of event marks from `c0`, e.g. when the route of a single page app changes,
+    /// so the names of marks stay short
+    #[cfg(feature = "timings")]
+    pub fn reset_event_counter(&self) {
+        self.shared.last_event_id.store(0, Ordering::Relaxed);
+    }
+
+    /// Get the number in the name of the mark of the next event, which is also the number of events
+    /// marked since the layer was created or the counter was reset
+    #[cfg(feature = "timings")]
+    pub fn current_event_id(&self) -> usize {
+        self.shared.last_event_id.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of spans which were created and not closed yet, to detect leaked spans
+    ///
+    /// A span stays open while a child span, or a clone of it, is alive, e.g. in a future which is never dropped.
+    pub fn open_span_count(&self) -> usize {
+        self.shared.open_spans.load(Ordering::Relaxed)
+    }
+
+    /// Summarize in the console the number of events suppressed by sampling or by the console
+    /// rate limit for each target since the last flush, and reset these counts
+    ///
+    /// Nothing is displayed if no event was suppressed. The counts are returned sorted by target.
+    pub fn flush_suppressed(&self) -> Vec<(&'static str, usize)> {
+        self.shared.flush_suppressed()
+    }
+
+    /// Set a trace context, e.g. from a W3C `traceparent`, whose ids are added as `trace_id`
+    /// and `span_id` fields to the following events, in the console and in the measure detail
+    ///
+    /// Events which record their own `trace_id` or `span_id` field keep it.
+    pub fn set_trace_context(&self, trace_id: &str, span_id: Option<&str>) {
+        let mut context = vec![("trace_id", trace_id.to_string())];
+        if let Some(span_id) = span_id {
+            context.push(("span_id", span_id.to_string()));
+        }
+        *self.shared.trace_context.lock().expect("trace context") = context;
+    }
+
+    /// Display the console messages buffered by [WASMLayerConfigBuilder::set_batch_console] now
+    #[cfg(feature = "console")]
+    pub fn flush_console(&self) {
+        self.shared.flush_console_batch();
+    }
+
+    /// Stop adding the ids of the trace context to events, see [WASMLayerHandle::set_trace_context]
+    pub fn clear_trace_context(&self) {
+        self.shared
+            .trace_context
+            .lock()
+            .expect("trace context")
+            .clear();
+    }
+}
+
+impl core::default::Default for WASMLayer {
+    fn default() -> Self {
+        WASMLayer::new(WASMLayerConfig::default())
+    }
+}
+
+// display the console messages still batched, e.g. when the subscriber of a test ends
+#[cfg(feature = "console")]
+impl Drop for WASMLayer {
+    fn drop(&mut self) {
+        if self.config.batch_console {
+            self.shared.flush_console_batch();
+        }
+    }
+}
+
+#[cfg(not(feature = "mark-with-rayon-thread-index"))]
+#[inline]
+fn thread_display_suffix() -> &'static str {
+    ""
+}
+#[cfg(feature = "mark-with-rayon-thread-index")]
+fn thread_display_suffix() -> String {
+    let mut message = " #".to_string();
+    match rayon::current_thread_index() {
+        Some(idx) => message.push_str(&format!("{}", idx)),
+        None => message.push_str("main"),
+    }
+    message
+}
+
+/// The target and `file:line` origin of an event, as far as the config shows them
+fn event_origin(config: &WASMLayerConfig, meta: &tracing::Metadata<'_>) -> Option<String> {
+    let location = if config.show_origin {
+        Some(
+            meta.file()
+                .and_then(|file| {
+                    let file = config.strip_path_prefix.strip(file);
+                    meta.line().map(|ln| format!("{}:{}", file, ln))
+                })
+                .unwrap_or_default(),
+        )
+    } else {
+        None
+    };
+    match (config.show_target, location) {
+        (true, Some(location)) => Some(format!("{} {}", meta.target(), location)),
+        (true, None) => Some(meta.target().to_string()),
+        (false, location) => location,
+    }
+}
+
+/// What the config shows of the spans an event occurred in
+#[derive(Default)]
+struct SpanContext {
+    breadcrumb: Option<String>,
+    fields: Option<String>,
+    /// Milliseconds since the span was created, see [WASMLayerConfigBuilder::set_show_span_relative_time]
+    elapsed_ms: Option<f64>,
+}
+
+impl SpanContext {
+    #[cfg(any(feature = "console", feature = "capture"))]
+    fn new<S: for<'a> LookupSpan<'a>>(
+        config: &WASMLayerConfig,
+        layer: LayerId,
+        span: Option<SpanRef<'_, S>>,
+    ) -> Self {
+        match span {
+            Some(span) => SpanContext {
+                breadcrumb: Some(span_breadcrumb(&span)).filter(|_| config.show_span_context),
+                fields: span_fields(&span, layer).filter(|_| config.show_span_fields),
+                elapsed_ms: layer
+                    .get::<CreatedAt>(&span.extensions())
+                    .map(|created_at| config.now_ms() - created_at.0),
+            },
+            None => SpanContext::default(),
+        }
+    }
+}
+
+/// The recorded message and fields of an event, between the breadcrumb and the fields of its spans
+struct EventMessage<'a> {
+    /// Tag shown after the level, see [MessagePrefixPosition::AfterLevel]
+    tag: Option<&'a str>,
+    span: &'a SpanContext,
+    body: &'a dyn fmt::Display,
+}
+
+impl<'a> core::fmt::Display for EventMessage<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if let Some(tag) = self.tag {
+            write!(f, " {}", tag)?;
+        }
+        match &self.span.breadcrumb {
+            Some(breadcrumb) => write!(f, " {}:{}", breadcrumb, self.body)?,
+            None => self.body.fmt(f)?,
+        }
+        if let Some(fields) = &self.span.fields {
+            write!(f, " {{{}}}", fields)?;
+        }
+        Ok(())
+    }
+}
+
+/// An event formatted the way [WASMLayer] reports it to the browser console, see [format_event]
+pub struct FormattedEvent<'a> {
+    config: &'a WASMLayerConfig,
+    prefix: String,
+    level: tracing::Level,
+    origin: Option<String>,
+    message: String,
+}
+
+impl<'a> FormattedEvent<'a> {
+    fn level_label(&self) -> &'static str {
+        self.config.level_label_style.label(&self.level)
+    }
+
+    /// The message with `%c` directives, to be logged followed by [FormattedEvent::styles]
+    pub fn styled(&self) -> String {
+        match &self.origin {
+            Some(origin) => format!(
+                "{}%c{}%c {}{}%c{}",
+                self.prefix,
+                self.level_label(),
+                origin,
+                thread_display_suffix(),
+                self.message,
+            ),
+            None => format!(
+                "{}%c{}%c{}{}",
+                self.prefix,
+                self.level_label(),
+                thread_display_suffix(),
+                self.message,
+            ),
+        }
+    }
+
+    /// The CSS style of each `%c` directive in [FormattedEvent::styled]
+    pub fn styles(&self) -> Vec<&'a str> {
+        let colors = &self.config.console_colors;
+        let mut styles = vec![colors.for_level(&self.level)];
+        if self.origin.is_some() {
+            styles.push(&colors.origin_style);
+        }
+        styles.push(&colors.message_style);
+        styles
+    }
+
+    /// The message colored with ANSI escape sequences
+    pub fn ansi(&self) -> String {
+        match &self.origin {
+            Some(origin) => format!(
+                "{}{}{}{} {}{}{}{}{}",
+                self.prefix,
+                ansi_color(&self.level),
+                self.level_label(),
+                ANSI_RESET,
+                ANSI_DIM,
+                origin,
+                thread_display_suffix(),
+                ANSI_RESET,
+                self.message,
+            ),
+            None => format!(
+                "{}{}{}{}{}{}",
+                self.prefix,
+                ansi_color(&self.level),
+                self.level_label(),
+                ANSI_RESET,
+                thread_display_suffix(),
+                self.message,
+            ),
+        }
+    }
+
+    /// Start the message with the sequence number of the event, if any
+    #[cfg(feature = "console")]
+    fn with_sequence(mut self, sequence: Option<usize>) -> Self {
+        self.prefix = sequence_prefix(sequence, self.prefix);
+        self
+    }
+
+    /// The message without any color styling
+    pub fn plain(&self) -> String {
+        plain_message(
+            &self.prefix,
+            self.level_label(),
+            self.origin.as_deref(),
+            &self.message,
+        )
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+/// Faint italic, like the CSS style of the origin
+const ANSI_DIM: &str = "\x1b[2;3m";
+
+/// ANSI escape sequence coloring the level label of events
+fn ansi_color(level: &tracing::Level) -> &'static str {
+    match *level {
+        tracing::Level::TRACE => "\x1b[35m",
+        tracing::Level::DEBUG => "\x1b[34m",
+        tracing::Level::INFO => "\x1b[32m",
+        tracing::Level::WARN => "\x1b[33m",
+        tracing::Level::ERROR => "\x1b[31m",
+    }
+}
+
+/// Format an event the way [WASMLayer] reports it to the browser console
+///
+/// The span breadcrumb and fields are left out, as the spans of an event are only known to the subscriber.
+pub fn format_event<'a>(
+    config: &'a WASMLayerConfig,
+    event: &tracing::Event<'_>,
+) -> FormattedEvent<'a> {
+    let mut recorder = StringRecorder::new(config);
+    event.record(&mut recorder);
+    format_recorded_event(config, event.metadata(), &SpanContext::default(), &recorder)
+}
+
+fn format_recorded_event<'a>(
+    config: &'a WASMLayerConfig,
+    meta: &tracing::Metadata<'_>,
+    span: &SpanContext,
+    recorder: &StringRecorder,
+) -> FormattedEvent<'a> {
+    FormattedEvent {
+        config,
+        prefix: event_prefix(config, span),
+        level: *meta.level(),
+        origin: event_origin(config, meta),
+        message: EventMessage {
+            tag: config_tag_after_level(config),
+            span,
+            body: recorder,
+        }
+        .to_string(),
+    }
+}
+
+/// The date, the timestamp, the time since the span was created and the tag shown before the level of an event,
+/// if the config shows them
+fn event_prefix(config: &WASMLayerConfig, span: &SpanContext) -> String {
+    let mut prefix = String::new();
+    if config.show_wall_clock {
+        write!(prefix, "{} ", iso_8601(date_now())).unwrap();
+    }
+    if config.show_timestamp {
+        write!(prefix, "{:.3} ", config.now_ms()).unwrap();
+    }
+    if let Some(elapsed_ms) = span.elapsed_ms {
+        write!(prefix, "+{:.1}ms ", elapsed_ms).unwrap();
+    }
+    if !config.message_prefix.is_empty()
+        && config.message_prefix_position == MessagePrefixPosition::BeforeLevel
+    {
+        write!(prefix, "{} ", config.message_prefix).unwrap();
+    }
+    prefix
+}
+
+/// Format milliseconds since the Unix epoch as a UTC date and time, like `Date.prototype.toISOString`
+fn iso_8601(epoch_ms: f64) -> String {
+    let epoch_ms = epoch_ms as i64;
+    let (days, ms_of_day) = (
+        epoch_ms.div_euclid(86_400_000),
+        epoch_ms.rem_euclid(86_400_000),
+    );
+    // civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        ms_of_day / 3_600_000,
+        ms_of_day / 60_000 % 60,
+        ms_of_day / 1000 % 60,
+        ms_of_day % 1000
+    )
+}
+
+/// The tag shown after the level of an event, if the config shows one there
+fn config_tag_after_level(config: &WASMLayerConfig) -> Option<&str> {
+    Some(config.message_prefix.as_str()).filter(|tag| {
+        !tag.is_empty() && config.message_prefix_position == MessagePrefixPosition::AfterLevel
+    })
+}
+
+#[cfg(feature = "console")]
+/// Prepend `#N` to the prefix of an event with a sequence number
+fn sequence_prefix(sequence: Option<usize>, prefix: String) -> String {
+    match sequence {
+        Some(sequence) => format!("#{} {}", sequence, prefix),
+        None => prefix,
+    }
+}
+
+/// Format an event without any console color styling
+fn plain_message(
+    prefix: &str,
+    level: &str,
+    origin: Option<&str>,
+    message: &dyn fmt::Display,
+) -> String {
+    match origin {
+        Some(origin) => format!(
+            "{}{} {}{} {}",
+            prefix,
+            level,
+            origin,
+            thread_display_suffix(),
+            message,
+        ),
+        None => format!("{}{}{} {}", prefix, level, thread_display_suffix(), message),
+    }
+}
+
+/// Store the fields of a new span and the time it was created, which the context of its events shows
+fn record_new_span<S: for<'a> LookupSpan<'a>>(
+    config: &WASMLayerConfig,
+    layer: LayerId,
+    record_fields: bool,
+    attrs: &tracing::span::Attributes<'_>,
+    span_ref: &SpanRef<'_, S>,
+) {
+    let mut recorder = StringRecorder::new(config);
+    if record_fields {
+        attrs.record(&mut recorder);
+    }
+    let mut extensions = span_ref.extensions_mut();
+    #[cfg(any(feature = "console", feature = "capture"))]
+    if config.show_span_relative_time {
+        layer.insert(&mut extensions, CreatedAt(config.now_ms()));
+    }
+    layer.insert(&mut extensions, recorder);
+}
+
+/// Add values recorded on a span after its creation to its fields
+fn record_span_values<S: for<'a> LookupSpan<'a>>(
+    layer: LayerId,
+    values: &tracing::span::Record<'_>,
+    span_ref: &SpanRef<'_, S>,
+) {
+    if let Some(recorder) = layer.get_mut::<StringRecorder>(&mut span_ref.extensions_mut()) {
+        values.record(recorder);
+    }
+}
+
+/// Drop the data stored by [record_new_span] when a span closes
+fn remove_span_data<S: for<'a> LookupSpan<'a>>(layer: LayerId, span_ref: &SpanRef<'_, S>) {
+    let mut extensions = span_ref.extensions_mut();
+    layer.remove::<StringRecorder>(&mut extensions);
+    #[cfg(any(feature = "console", feature = "capture"))]
+    layer.remove::<CreatedAt>(&mut extensions);
+}
+
+#[cfg(any(feature = "console", feature = "capture"))]
+/// Build a `root > leaf` breadcrumb of the spans an event occurred in
+fn span_breadcrumb<S: for<'a> LookupSpan<'a>>(span: &SpanRef<'_, S>) -> String {
+    let mut breadcrumb = String::new();
+    for (idx, span_ref) in span.scope().from_root().enumerate() {
+        if idx > 0 {
+            breadcrumb.push_str(" > ");
+        }
+        breadcrumb.push_str(span_ref.name());
+    }
+    breadcrumb
+}
+
+#[cfg(any(feature = "console", feature = "capture"))]
+/// Join the fields currently recorded by a layer on the spans an event occurred in, from the root
+fn span_fields<S: for<'a> LookupSpan<'a>>(span: &SpanRef<'_, S>, layer: LayerId) -> Option<String> {
+    let mut fields = String::new();
+    for span_ref in span.scope().from_root() {
+        if let Some(recorder) = layer.get::<StringRecorder>(&span_ref.extensions()) {
+            for (name, value) in &recorder.fields {
+                if !fields.is_empty() {
+                    fields.push(' ');
+                }
+                fields.push_str(&recorder.format_field(name, value));
+            }
+        }
+    }
+    Some(fields).filter(|fields| !fields.is_empty())
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for WASMLayer {
+    /// Called for spans as well as events, a disabled span is not created so it has no marks nor console group
+    fn enabled(&self, metadata: &tracing::Metadata<'_>, _: Context<'_, S>) -> bool {
+        self.console_enabled(metadata) || self.timings_enabled(metadata)
+    }
+
+    /// The most verbose of the maximal level and the target directives, so callsites above it are skipped statically
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        let default_max_level = match &self.config.enabled_levels {
+            Some(enabled_levels) => enabled_levels
+                .iter()
+                .map(|level| LevelFilter::from(*level))
+                .max()
+                .unwrap_or(LevelFilter::OFF),
+            None => self.shared.max_level().into(),
+        };
+        let max_level = self
+            .config
+            .target_directives
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(default_max_level, core::cmp::max);
+        let max_level = match (self.config.console_max_level, self.config.timings_max_level) {
+            (Some(console_max_level), Some(timings_max_level)) => {
+                max_level.min(console_max_level.max(timings_max_level).into())
+            }
+            _ => max_level,
+        };
+        Some(max_level)
+    }
+
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::Id,
+        ctx: Context<'_, S>,
+    ) {
+        self.shared.open_spans.fetch_add(1, Ordering::Relaxed);
+        if let Some(span_ref) = ctx.span(id) {
+            record_new_span(
+                &self.config,
+                self.id,
+                self.config.records_span_fields(),
+                attrs,
+                &span_ref,
+            );
+            // spans without fields when they are created are measured
+            #[cfg(feature = "console")]
+            if self.config.table_for_spans
+                && self.config.report_logs_in_console
+                && self.console_enabled(span_ref.metadata())
+                && matches!(
+                    self.id.get::<StringRecorder>(&span_ref.extensions()),
+                    Some(debug_record) if !debug_record.fields.is_empty()
+                )
+            {
+                self.id.insert(&mut span_ref.extensions_mut(), TabledSpan);
+            }
+            #[cfg(feature = "console")]
+            if self.config.log_span_busy_time
+                && self.config.report_logs_in_console
+                && self.console_enabled(span_ref.metadata())
+            {
+                self.id.insert(
+                    &mut span_ref.extensions_mut(),
+                    BusyTime {
+                        created_at: self.config.now_ms(),
+                        busy: 0.0,
+                        entered_at: None,
+                        entered: 0,
+                    },
                 );
             }
         }
     }
-    // /// doc: Notifies this layer that the span with the given ID has been closed.
-    // /// We can dispose of any data for the span we might have here...
-    // fn on_close(&self, _id: tracing::Id, ctx: Context<'_, S>) {}
+
+    /// doc: Notifies this layer that a span with the given Id recorded the given values.
+    fn on_record(&self, id: &tracing::Id, values: &tracing::span::Record<'_>, ctx: Context<'_, S>) {
+        if !self.config.records_span_fields() {
+            return;
+        }
+        if let Some(span_ref) = ctx.span(id) {
+            record_span_values(self.id, values, &span_ref);
+        }
+    }
+
+    // /// doc: Notifies this layer that a span with the ID span recorded that it follows from the span with the ID follows.
+    // fn on_follows_from(&self, _span: &tracing::Id, _follows: &tracing::Id, ctx: Context<'_, S>) {}
+    /// doc: Notifies this layer that an event has occurred.
+    #[cfg_attr(not(feature = "console"), allow(unused_variables))]
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        // events emitted while reporting an event, e.g. by a `Debug` implementation or the event hook,
+        // are dropped rather than recursing
+        let _reporting = match ReportingEvent::enter() {
+            Some(reporting) => reporting,
+            None => return,
+        };
+        self.auto_flush_suppressed();
+        if !self.sample(event.metadata().target()) {
+            return;
+        }
+        #[cfg(feature = "indexeddb-sink")]
+        let stored = self.config.indexeddb_sink.is_some();
+        #[cfg(not(feature = "indexeddb-sink"))]
+        let stored = false;
+        if self.config.report_logs_in_timings
+            || self.config.report_logs_in_console
+            || self.config.always_console_from.is_some()
+            || self.config.on_event_hook.is_some()
+            || stored
+        {
+            // the allocations of a scratch recorder are reused, events are not reported reentrantly
+            SCRATCH_RECORDER.with(|scratch| {
+                let mut recorder = scratch.borrow_mut();
+                recorder.reset(&self.config);
+                self.report_event(event, &mut recorder, &ctx);
+            });
+        }
+    }
+    /// doc: Notifies this layer that a span with the given ID was entered.
+    #[cfg_attr(
+        not(any(feature = "console", feature = "timings")),
+        allow(unused_variables)
+    )]
+    fn on_enter(&self, id: &tracing::Id, ctx: Context<'_, S>) {
+        #[cfg(feature = "console")]
+        if self.config.report_logs_in_console {
+            if let Some(span_ref) = ctx
+                .span(id)
+                .filter(|span_ref| self.console_enabled(span_ref.metadata()))
+            {
+                self.log_span_enter(&span_ref);
+            }
+        }
+        #[cfg(feature = "timings")]
+        if self.config.report_spans_in_timings
+            && matches!(ctx.span(id), Some(span_ref) if self.measures_span(&span_ref))
+        {
+            self.mark_span_enter(id);
+        }
+    }
+    /// doc: Notifies this layer that the span with the given ID was exited.
+    #[cfg_attr(
+        not(any(feature = "console", feature = "timings")),
+        allow(unused_variables)
+    )]
+    fn on_exit(&self, id: &tracing::Id, ctx: Context<'_, S>) {
+        if let Some(span_ref) = ctx.span(id) {
+            #[cfg(feature = "console")]
+            self.log_span_exit(&span_ref);
+            #[cfg(feature = "timings")]
+            if self.config.report_spans_in_timings && self.measures_span(&span_ref) {
+                self.measure_span(id, &span_ref);
+            }
+        }
+    }
+    /// doc: Notifies this layer that the span with the given ID has been closed.
+    fn on_close(&self, id: tracing::Id, ctx: Context<'_, S>) {
+        self.shared.open_spans.fetch_sub(1, Ordering::Relaxed);
+        if let Some(span_ref) = ctx.span(&id) {
+            #[cfg(feature = "console")]
+            self.log_span_close(&span_ref);
+            remove_span_data(self.id, &span_ref);
+            #[cfg(feature = "console")]
+            {
+                let mut extensions = span_ref.extensions_mut();
+                self.id.remove::<OpenConsoleGroups>(&mut extensions);
+                self.id.remove::<EnteredAt>(&mut extensions);
+            }
+        }
+    }
     // /// doc: Notifies this layer that a span ID has been cloned, and that the subscriber returned a different ID.
     // /// I'm not sure if I need to do something here...
     // fn on_id_change(&self, _old: &tracing::Id, _new: &tracing::Id, ctx: Context<'_, S>) {}
 }
 
+/// A [WASMLayer] which can be cloned to be used by several subscribers, e.g. when the subscriber is rebuilt
+/// on hot reload, while keeping the same config and counters
+///
+/// The subscriber can be downcast to the inner [WASMLayer], so [install_panic_hook] still flushes its buffered events.
+#[derive(Clone)]
+pub struct SharedWASMLayer(Arc<WASMLayer>);
+
+impl SharedWASMLayer {
+    pub fn new(layer: WASMLayer) -> Self {
+        SharedWASMLayer(Arc::new(layer))
+    }
+
+    /// Get a handle to change the configuration of this layer while it is in use
+    pub fn handle(&self) -> WASMLayerHandle {
+        self.0.handle()
+    }
+}
+
+impl From<WASMLayer> for SharedWASMLayer {
+    fn from(layer: WASMLayer) -> Self {
+        SharedWASMLayer::new(layer)
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SharedWASMLayer {
+    fn enabled(&self, metadata: &tracing::Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        Layer::<S>::enabled(&*self.0, metadata, ctx)
+    }
+
+    fn max_level_hint(&self) -> Option<tracing::level_filters::LevelFilter> {
+        Layer::<S>::max_level_hint(&*self.0)
+    }
+
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::Id,
+        ctx: Context<'_, S>,
+    ) {
+        self.0.on_new_span(attrs, id, ctx)
+    }
+
+    fn on_record(&self, id: &tracing::Id, values: &tracing::span::Record<'_>, ctx: Context<'_, S>) {
+        self.0.on_record(id, values, ctx)
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        self.0.on_event(event, ctx)
+    }
+
+    fn on_enter(&self, id: &tracing::Id, ctx: Context<'_, S>) {
+        self.0.on_enter(id, ctx)
+    }
+
+    fn on_exit(&self, id: &tracing::Id, ctx: Context<'_, S>) {
+        self.0.on_exit(id, ctx)
+    }
+
+    fn on_close(&self, id: tracing::Id, ctx: Context<'_, S>) {
+        self.0.on_close(id, ctx)
+    }
+
+    unsafe fn downcast_raw(&self, id: core::any::TypeId) -> Option<*const ()> {
+        if id == core::any::TypeId::of::<Self>() {
+            Some(self as *const Self as *const ())
+        } else if id == core::any::TypeId::of::<WASMLayer>() {
+            Some(&*self.0 as *const WASMLayer as *const ())
+        } else {
+            None
+        }
+    }
+}
+
+/// Create a [WASMLayer] for composing with other layers, rather than setting it as the global default
+///
+/// ```no_run
+/// use tracing_subscriber::filter::LevelFilter;
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let subscriber = tracing_subscriber::Registry::default()
+///     .with(tracing_wasm::wasm_layer(tracing_wasm::WASMLayerConfig::default()))
+///     .with(LevelFilter::INFO);
+///
+/// tracing::subscriber::set_global_default(subscriber).expect("default global");
+/// ```
+pub fn wasm_layer(config: WASMLayerConfig) -> WASMLayer {
+    WASMLayer::new(config)
+}
+
+/// Set by the functions of this crate which set the global default, see [is_installed]
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the global default was set by one of the `set_as_global_default*` or `init*` functions of this crate,
+/// which panic if it is set twice
+///
+/// A global default set directly with [tracing::subscriber::set_global_default] is not detected.
+///
+/// ```no_run
+/// if !tracing_wasm::is_installed() {
+///     tracing_wasm::init();
+/// }
+/// ```
+pub fn is_installed() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
 /// Set the global default with [tracing::subscriber::set_global_default]
+///
+/// The global default is per wasm instance, so each Web Worker has to set its own.
 pub fn set_as_global_default() {
-    tracing::subscriber::set_global_default(
-        Registry::default().with(WASMLayer::new(WASMLayerConfig::default())),
-    )
-    .expect("default global");
+    try_set_as_global_default().expect("default global");
 }
 
 /// Set the global default with [tracing::subscriber::set_global_default]
 pub fn try_set_as_global_default() -> Result<(), SetGlobalDefaultError> {
-    tracing::subscriber::set_global_default(
-        Registry::default().with(WASMLayer::new(WASMLayerConfig::default())),
-    )
+    try_set_as_global_default_with_config(WASMLayerConfig::default())
 }
 
 /// Set the global default with [tracing::subscriber::set_global_default]
 pub fn set_as_global_default_with_config(config: WASMLayerConfig) {
-    tracing::subscriber::set_global_default(Registry::default().with(WASMLayer::new(config)))
+    try_set_as_global_default_with_config(config).expect("default global");
+}
+
+/// Set the global default with [tracing::subscriber::set_global_default], returning a handle to reconfigure it later
+pub fn set_as_global_default_with_handle(config: WASMLayerConfig) -> WASMLayerHandle {
+    let layer = WASMLayer::new(config);
+    let handle = layer.handle();
+    tracing::subscriber::set_global_default(Registry::default().with(layer))
         .expect("default global");
+    INSTALLED.store(true, Ordering::Relaxed);
+    #[cfg(feature = "js-api")]
+    js_api::set_global_handle(handle.clone());
+    handle
+}
+
+/// Set the global default with [tracing::subscriber::set_global_default]
+pub fn try_set_as_global_default_with_config(
+    config: WASMLayerConfig,
+) -> Result<(), SetGlobalDefaultError> {
+    let layer = WASMLayer::new(config);
+    #[cfg(feature = "js-api")]
+    let handle = layer.handle();
+    tracing::subscriber::set_global_default(Registry::default().with(layer))?;
+    INSTALLED.store(true, Ordering::Relaxed);
+    #[cfg(feature = "js-api")]
+    js_api::set_global_handle(handle);
+    Ok(())
+}
+
+/// Set the global default with the default config, same as [set_as_global_default]
+pub fn init() {
+    set_as_global_default();
+}
+
+/// Set the global default with the default config, except for the maximal level of the displayed events
+///
+/// ```no_run
+/// tracing_wasm::init_with_level(tracing::Level::DEBUG);
+/// ```
+pub fn init_with_level(max_level: tracing::Level) {
+    set_as_global_default_with_config(
+        WASMLayerConfigBuilder::new()
+            .set_max_level(max_level)
+            .build(),
+    );
+}
+
+/// Set the global default with the default config as early as possible, returning a handle to adjust it
+/// once the rest of the configuration is known
+///
+/// Events emitted before a global default is set are lost, so call this first thing in your
+/// `#[wasm_bindgen(start)]` function, before any other initialization which could log. The global default
+/// can only be set once, so the other `set_as_global_default*` functions fail afterwards.
+///
+/// ```no_run
+/// let handle = tracing_wasm::init_early();
+/// tracing::info!("loading the settings");
+/// handle.set_max_level(tracing::Level::INFO);
+/// ```
+pub fn init_early() -> WASMLayerHandle {
+    set_as_global_default_with_handle(WASMLayerConfig::default())
+}
+
+/// Set the default of the current thread with [tracing::subscriber::set_default]
+///
+/// The previous default is restored when the returned guard is dropped.
+pub fn set_as_default() -> DefaultGuard {
+    set_as_default_with_config(WASMLayerConfig::default())
+}
+
+/// Set the default of the current thread with [tracing::subscriber::set_default]
+///
+/// The previous default is restored when the returned guard is dropped.
+pub fn set_as_default_with_config(config: WASMLayerConfig) -> DefaultGuard {
+    tracing::subscriber::set_default(Registry::default().with(WASMLayer::new(config)))
+}
+
+/// Log a JS value, such as an `Error`, with the console method of `level`,
+/// so the browser console displays it interactively rather than as a string
+///
+/// The value is logged directly, without going through the subscriber. TRACE uses `console.debug`,
+/// as `console.trace` would add a stack trace.
+///
+/// ```no_run
+/// let error = js_sys::Error::new("request failed");
+/// tracing_wasm::log_js_value(tracing::Level::ERROR, &error);
+/// ```
+#[cfg(feature = "console")]
+pub fn log_js_value(level: tracing::Level, value: &JsValue) {
+    let method = match level {
+        tracing::Level::TRACE | tracing::Level::DEBUG => ConsoleMethod::Debug,
+        tracing::Level::INFO => ConsoleMethod::Info,
+        tracing::Level::WARN => ConsoleMethod::Warn,
+        tracing::Level::ERROR => ConsoleMethod::Error,
+    };
+    method.log_variadic(&js_sys::Array::of1(value));
 }
 
+/// Records the message of an event or span apart from its other fields, which keep the order
+/// they were recorded in, so the message is always displayed first
+#[derive(Default)]
 struct StringRecorder {
-    display: String,
-    is_following_args: bool,
+    message: Option<String>,
+    fields: Vec<(&'static str, String)>,
+    max_field_len: Option<usize>,
+    max_message_len: Option<usize>,
+    redacted_fields: Arc<Vec<String>>,
+    single_line: bool,
+    field_formatter: Option<FieldFormatterRef>,
+    unquoted_strings: bool,
+    /// Console group opened or closed by the event, see [GROUP_FIELD]
+    #[cfg(feature = "console")]
+    group: Option<GroupCommand>,
+    /// Cleared buffers of the message and field values of the previous event, see [StringRecorder::reset]
+    spare_buffers: Vec<String>,
 }
 impl StringRecorder {
-    fn new() -> Self {
+    fn new(config: &WASMLayerConfig) -> Self {
         StringRecorder {
-            display: String::new(),
-            is_following_args: false,
+            max_field_len: config.max_field_len,
+            max_message_len: config.max_message_len,
+            redacted_fields: config.redacted_fields.clone(),
+            single_line: config.single_line,
+            field_formatter: config.field_formatter.clone(),
+            unquoted_strings: !config.quote_strings,
+            ..StringRecorder::default()
+        }
+    }
+
+    /// Forget what was recorded and take the settings of `config`, keeping the allocated capacity
+    /// of the fields and of the message and field value buffers
+    fn reset(&mut self, config: &WASMLayerConfig) {
+        let mut fields = core::mem::take(&mut self.fields);
+        let mut spare_buffers = core::mem::take(&mut self.spare_buffers);
+        spare_buffers.extend(
+            fields
+                .drain(..)
+                .map(|(_, value)| value)
+                .chain(self.message.take()),
+        );
+        for buffer in &mut spare_buffers {
+            buffer.clear();
+        }
+        *self = StringRecorder {
+            fields,
+            spare_buffers,
+            ..StringRecorder::new(config)
+        };
+    }
+
+    /// Format a recorded field with the formatter of the config
+    fn format_field(&self, name: &str, value: &str) -> String {
+        match &self.field_formatter {
+            Some(formatter) => formatter.0.format(name, &RecordedValue(value)),
+            None => DefaultFieldFormatter.format(name, &RecordedValue(value)),
+        }
+    }
+
+    /// Add the fields which were not recorded, formatted like recorded string fields
+    fn add_missing_fields(&mut self, fields: &[(&'static str, String)]) {
+        for (name, value) in fields {
+            if !self.fields.iter().any(|(recorded, _)| recorded == name) {
+                self.fields.push((name, format!("{:?}", value)));
+            }
+        }
+    }
+
+    /// Add the fields of a span which were not recorded, before the recorded ones
+    fn inherit_fields(&mut self, span_fields: &[(&'static str, String)]) {
+        let inherited: Vec<_> = span_fields
+            .iter()
+            .filter(|(name, _)| !self.fields.iter().any(|(recorded, _)| recorded == name))
+            .cloned()
+            .collect();
+        self.fields.splice(0..0, inherited);
+    }
+
+    fn format_value(&mut self, value: &dyn fmt::Debug, max_len: Option<usize>) -> String {
+        let mut buffer = self.spare_buffers.pop().unwrap_or_default();
+        write_debug(&mut buffer, value);
+        let value = buffer;
+        if self.single_line && value.contains('\n') {
+            truncate(value.replace('\n', " ⏎ "), max_len)
+        } else {
+            truncate(value, max_len)
+        }
+    }
+}
+
+#[cfg(feature = "console")]
+impl StringRecorder {
+    /// Display the recorded fields with `console.table`
+    fn log_table(&self) {
+        if !self.fields.is_empty() {
+            table_fields(self);
         }
     }
 }
 
+impl StringRecorder {
+    /// The recorded fields as a JS object of strings
+    #[cfg(all(target_arch = "wasm32", any(feature = "console", feature = "timings")))]
+    fn fields_object(&self) -> js_sys::Object {
+        let data = js_sys::Object::new();
+        for (name, value) in &self.fields {
+            let _ =
+                js_sys::Reflect::set(&data, &JsValue::from_str(name), &JsValue::from_str(value));
+        }
+        data
+    }
+}
+
+/// Display the fields of a recorder with `console.table`
+#[cfg(all(target_arch = "wasm32", feature = "console"))]
+fn table_fields(recorder: &StringRecorder) {
+    table(&recorder.fields_object());
+}
+
+/// Format a value with its `Debug` implementation, with a placeholder if it fails or panics,
+/// rather than unwinding through the callsite of the event
+///
+/// Panics are only caught where they unwind, `wasm32-unknown-unknown` aborts on panic unless built with `-C panic=unwind`.
+#[cfg(feature = "console")]
+fn debug_string(value: &dyn fmt::Debug) -> String {
+    let mut formatted = String::new();
+    write_debug(&mut formatted, value);
+    formatted
+}
+
+/// Append a value formatted like [debug_string] to `buffer`, e.g. a buffer reused across events
+fn write_debug(buffer: &mut String, value: &dyn fmt::Debug) {
+    const PLACEHOLDER: &str = "<formatting error>";
+    let start = buffer.len();
+    let written = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        write!(buffer, "{:?}", value)
+    }));
+    if !matches!(written, Ok(Ok(()))) {
+        buffer.truncate(start);
+        buffer.push_str(PLACEHOLDER);
+    }
+}
+
+/// Truncate `value` to at most `max_len` bytes, noting how many bytes were cut
+fn truncate(mut value: String, max_len: Option<usize>) -> String {
+    if let Some(max_len) = max_len {
+        if value.len() > max_len {
+            let mut end = max_len;
+            while !value.is_char_boundary(end) {
+                end -= 1;
+            }
+            let cut = value.len() - end;
+            value.truncate(end);
+            write!(value, "…({} more bytes)", cut).unwrap();
+        }
+    }
+    value
+}
+
 impl Visit for StringRecorder {
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
         if field.name() == "message" {
-            if !self.display.is_empty() {
-                self.display = format!("{:?}\n{}", value, self.display)
-            } else {
-                self.display = format!("{:?}", value)
-            }
+            self.message = Some(self.format_value(value, self.max_message_len));
+        } else if self.redacted_fields.iter().any(|name| name == field.name()) {
+            self.fields.push((field.name(), "<redacted>".to_string()));
         } else {
-            if self.is_following_args {
-                // following args
-                writeln!(self.display).unwrap();
-            } else {
-                // first arg
-                write!(self.display, " ").unwrap();
-                self.is_following_args = true;
-            }
-            write!(self.display, "{} = {:?};", field.name(), value).unwrap();
+            let value = self.format_value(value, self.max_field_len);
+            self.fields.push((field.name(), value));
+        }
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.record_debug(field, &ErrorChain(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        #[cfg(feature = "console")]
+        if let Some(command) = GroupCommand::from_field(field, value) {
+            self.group = Some(command);
+            return;
+        }
+        if self.unquoted_strings {
+            self.record_debug(field, &RecordedValue(value));
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+}
+
+/// Reserved field of events which open or close a console group instead of being displayed:
+/// `__group = "open"` opens a group labelled with the message of the event, and
+/// `__group = "close"` closes the last opened group
+#[cfg(feature = "console")]
+const GROUP_FIELD: &str = "__group";
+
+#[cfg(feature = "console")]
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum GroupCommand {
+    Open,
+    Close,
+}
+
+#[cfg(feature = "console")]
+impl GroupCommand {
+    fn from_field(field: &Field, value: &str) -> Option<Self> {
+        match (field.name(), value) {
+            (GROUP_FIELD, "open") => Some(GroupCommand::Open),
+            (GROUP_FIELD, "close") => Some(GroupCommand::Close),
+            _ => None,
+        }
+    }
+}
+
+/// Formats an error followed by its `source()` chain, one cause per line
+struct ErrorChain<'a>(&'a (dyn std::error::Error + 'static));
+
+// Debug so the chain is formatted like other field values, see [StringRecorder::format_value]
+impl<'a> fmt::Debug for ErrorChain<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = self.0.source();
+        while let Some(error) = source {
+            write!(f, "\n    caused by: {}", error)?;
+            source = error.source();
         }
+        Ok(())
     }
 }
 
 impl core::fmt::Display for StringRecorder {
     fn fmt(&self, mut f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        if !self.display.is_empty() {
-            write!(&mut f, " {}", self.display)
+        if let Some(message) = &self.message {
+            write!(&mut f, " {}", message)?;
+        } else if !self.fields.is_empty() {
+            write!(&mut f, " ")?;
+        }
+        for (idx, (name, value)) in self.fields.iter().enumerate() {
+            if idx == 0 {
+                // first arg
+                write!(&mut f, " ")?;
+            } else if self.single_line {
+                write!(&mut f, " ")?;
+            } else {
+                // following args
+                writeln!(&mut f)?;
+            }
+            f.write_str(&self.format_field(name, value))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "console")]
+/// Records the fields of an event as JS values, so the browser console can display them interactively
+struct JsRecorder<'a> {
+    config: &'a WASMLayerConfig,
+    message: Option<String>,
+    fields: Vec<(&'static str, JsValue)>,
+}
+
+#[cfg(feature = "console")]
+impl<'a> JsRecorder<'a> {
+    fn new(config: &'a WASMLayerConfig) -> Self {
+        JsRecorder {
+            config,
+            message: None,
+            fields: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, field: &Field, value: impl FnOnce() -> JsValue) {
+        if self
+            .config
+            .redacted_fields
+            .iter()
+            .any(|name| name == field.name())
+        {
+            self.fields
+                .push((field.name(), JsValue::from_str("<redacted>")));
         } else {
-            Ok(())
+            self.fields.push((field.name(), value()));
         }
     }
 }
 
-impl core::default::Default for StringRecorder {
-    fn default() -> Self {
-        StringRecorder::new()
+#[cfg(feature = "console")]
+impl<'a> Visit for JsRecorder<'a> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, || JsValue::from_f64(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, || JsValue::from_f64(value as f64));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, || JsValue::from_f64(value as f64));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, || JsValue::from_bool(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(truncate(value.to_string(), self.config.max_message_len));
+        } else {
+            let max_field_len = self.config.max_field_len;
+            self.push(field, || {
+                JsValue::from(truncate(value.to_string(), max_field_len))
+            });
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(truncate(debug_string(value), self.config.max_message_len));
+        } else {
+            let max_field_len = self.config.max_field_len;
+            self.push(field, || {
+                JsValue::from(truncate(debug_string(value), max_field_len))
+            });
+        }
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.push(field, || js_error(value).into());
+    }
+}
+
+/// Records the fields of an event both as text and as JS values, in a single pass
+#[cfg(feature = "console")]
+struct TeeRecorder<'r, 'c> {
+    text: &'r mut StringRecorder,
+    js: &'r mut JsRecorder<'c>,
+}
+
+#[cfg(feature = "console")]
+impl<'r, 'c> Visit for TeeRecorder<'r, 'c> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.text.record_f64(field, value);
+        self.js.record_f64(field, value);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.text.record_i64(field, value);
+        self.js.record_i64(field, value);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.text.record_u64(field, value);
+        self.js.record_u64(field, value);
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.text.record_bool(field, value);
+        self.js.record_bool(field, value);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.text.record_str(field, value);
+        self.js.record_str(field, value);
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.text.record_error(field, value);
+        self.js.record_error(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.text.record_debug(field, value);
+        self.js.record_debug(field, value);
+    }
+}
+
+/// The fields of an event as JS values, in the order of `recorder`: the span fields it inherits, its own
+/// fields, then the trace context it doesn't record itself
+#[cfg(feature = "console")]
+fn js_fields(
+    recorder: &StringRecorder,
+    js_recorder: &JsRecorder<'_>,
+    trace_context: &[(&'static str, String)],
+) -> Vec<(&'static str, JsValue)> {
+    recorder
+        .fields
+        .iter()
+        .map(|(name, formatted)| {
+            let recorded = js_recorder
+                .fields
+                .iter()
+                .find(|(recorded, _)| recorded == name)
+                .map(|(_, value)| value.clone());
+            let value = recorded.unwrap_or_else(|| {
+                match trace_context.iter().find(|(traced, _)| traced == name) {
+                    Some((_, value)) => JsValue::from_str(value),
+                    // inherited from the span, which only keeps the formatted value
+                    None => JsValue::from_str(formatted),
+                }
+            });
+            (*name, value)
+        })
+        .collect()
+}
+
+/// Convert an error to a JS `Error`, with its source as `cause` so devtools show the whole chain
+#[cfg(feature = "console")]
+fn js_error(error: &(dyn std::error::Error + 'static)) -> js_sys::Error {
+    let converted = js_sys::Error::new(&error.to_string());
+    if let Some(source) = error.source() {
+        let _ = js_sys::Reflect::set(&converted, &"cause".into(), &js_error(source).into());
+    }
+    converted
+}
+
+#[cfg(feature = "console")]
+impl<'a> core::fmt::Display for JsRecorder<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, " {}", message),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "console")]
+/// Serialize an event as a single line JSON object
+fn json_event(meta: &tracing::Metadata<'_>, recorder: &StringRecorder) -> String {
+    let mut json = String::new();
+    write!(json, "{{\"level\":\"{}\",\"target\":", meta.level()).unwrap();
+    write_json_string(&mut json, meta.target());
+    json.push_str(",\"file\":");
+    match meta.file() {
+        Some(file) => write_json_string(&mut json, file),
+        None => json.push_str("null"),
+    }
+    json.push_str(",\"line\":");
+    match meta.line() {
+        Some(line) => write!(json, "{}", line).unwrap(),
+        None => json.push_str("null"),
+    }
+    json.push_str(",\"message\":");
+    match &recorder.message {
+        Some(message) => write_json_string(&mut json, message),
+        None => json.push_str("null"),
+    }
+    json.push_str(",\"fields\":{");
+    for (idx, (name, value)) in recorder.fields.iter().enumerate() {
+        if idx > 0 {
+            json.push(',');
+        }
+        write_json_string(&mut json, name);
+        json.push(':');
+        write_json_string(&mut json, value);
+    }
+    json.push_str("}}");
+    json
+}
+
+/// Format an event like the default format of the `tracing-subscriber` fmt layer without timestamps,
+/// see [ConsoleOutputFormat::Fmt]
+#[cfg(feature = "console")]
+fn fmt_event<S: for<'a> LookupSpan<'a>>(
+    meta: &tracing::Metadata<'_>,
+    recorder: &StringRecorder,
+    layer: LayerId,
+    span: Option<SpanRef<'_, S>>,
+) -> String {
+    let mut line = format!("{:>5} ", meta.level());
+    if let Some(span) = span {
+        for span_ref in span.scope().from_root() {
+            line.push_str(span_ref.name());
+            if let Some(span_recorder) = layer.get::<StringRecorder>(&span_ref.extensions()) {
+                if !span_recorder.fields.is_empty() {
+                    line.push('{');
+                    write_fmt_fields(&mut line, &span_recorder.fields);
+                    line.push('}');
+                }
+            }
+            line.push(':');
+        }
+        line.push(' ');
+    }
+    write!(line, "{}: ", meta.target()).unwrap();
+    if let Some(message) = &recorder.message {
+        line.push_str(message);
+        if !recorder.fields.is_empty() {
+            line.push(' ');
+        }
+    }
+    write_fmt_fields(&mut line, &recorder.fields);
+    line
+}
+
+/// Write `name=value` pairs separated by spaces, see [ConsoleOutputFormat::Fmt]
+#[cfg(feature = "console")]
+fn write_fmt_fields(line: &mut String, fields: &[(&'static str, String)]) {
+    for (idx, (name, value)) in fields.iter().enumerate() {
+        if idx > 0 {
+            line.push(' ');
+        }
+        write!(line, "{}={}", name, value).unwrap();
+    }
+}
+
+#[cfg(feature = "console")]
+fn write_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
     }
+    json.push('"');
 }
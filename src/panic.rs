@@ -0,0 +1,60 @@
+//! Reporting panics through the layer, so they are formatted like other events and buffered events are not lost
+
+use std::any::Any;
+use std::panic::Location;
+use std::sync::Once;
+
+#[cfg(feature = "console")]
+use super::WASMLayer;
+
+/// Set the panic hook once, as each call would otherwise chain it again
+static INSTALL_PANIC_HOOK: Once = Once::new();
+
+/// Set a panic hook which logs the panic message and location at ERROR through the layer of the current
+/// subscriber, then displays the buffered events, before calling the previous panic hook
+///
+/// Buffered events are the console messages of [WASMLayerConfigBuilder::set_batch_console](super::WASMLayerConfigBuilder::set_batch_console),
+/// including the panic itself, and the entries waiting to be written by the `indexeddb-sink` feature. Nothing is
+/// flushed for a subscriber without a [WASMLayer](super::WASMLayer), but the panic is still logged.
+///
+/// The panic is logged with the `panic` target and a `location` field, e.g. `src/lib.rs:10:5`. Panics caught with
+/// [std::panic::catch_unwind] are logged too, as the hook is called before unwinding. Only the first call sets the hook.
+///
+/// `console_error_panic_hook` displays the panic with its stack trace in `console.error`, it is complementary:
+/// call `console_error_panic_hook::set_once()` before this function, so it is chained after this hook. A hook set
+/// after this function replaces it, unless it chains the previous hook too.
+///
+/// ```no_run
+/// tracing_wasm::set_as_global_default();
+/// tracing_wasm::install_panic_hook();
+/// ```
+pub fn install_panic_hook() {
+    INSTALL_PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            report_panic(info.payload(), info.location());
+            previous(info);
+        }));
+    });
+}
+
+/// The hook info is passed by parts, as its type was renamed from `PanicInfo` to `PanicHookInfo` in Rust 1.81
+fn report_panic(payload: &(dyn Any + Send), location: Option<&Location<'_>>) {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    match location {
+        Some(location) => tracing::error!(target: "panic", %location, "{}", message),
+        None => tracing::error!(target: "panic", "{}", message),
+    }
+    #[cfg(feature = "console")]
+    tracing::dispatcher::get_default(|dispatch| {
+        if let Some(layer) = dispatch.downcast_ref::<WASMLayer>() {
+            layer.shared.flush_console_batch();
+        }
+    });
+    #[cfg(feature = "indexeddb-sink")]
+    super::indexeddb::write_all_pending();
+}
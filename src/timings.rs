@@ -0,0 +1,467 @@
+//! Reporting events and spans as `performance` marks and measures, so they show up in the browser profiler
+
+use core::fmt::Write;
+#[cfg(feature = "console")]
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::host::{
+    mark, mark_with_options, measure, measure2, measure_from_origin, measure_with_options,
+};
+use super::{thread_display_suffix, MeasureNameTemplate, StringRecorder, WASMLayer};
+#[cfg(feature = "console")]
+use super::{warn1, TabledSpan};
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance)]
+    fn mark(a: &str);
+    #[wasm_bindgen(js_namespace = performance, js_name = mark)]
+    fn mark_with_js_options(name: &str, options: &JsValue);
+    #[wasm_bindgen(catch, js_namespace = performance)]
+    fn measure(name: String, startMark: String) -> Result<(), JsValue>;
+    #[wasm_bindgen(catch, js_namespace = performance, js_name = measure)]
+    fn measure2(name: String, startMark: &str, endMark: &str) -> Result<(), JsValue>;
+    #[wasm_bindgen(catch, js_namespace = performance, js_name = measure)]
+    fn measure_from_origin(name: String) -> Result<(), JsValue>;
+    #[wasm_bindgen(catch, js_namespace = performance, js_name = measure)]
+    fn measure_with_js_options(name: String, options: &JsValue) -> Result<(), JsValue>;
+}
+
+#[cfg(target_arch = "wasm32")]
+fn mark_with_options(name: &str, options: &PerformanceOptions<'_>) {
+    mark_with_js_options(name, &options.to_js());
+}
+
+#[cfg(target_arch = "wasm32")]
+fn measure_with_options(name: String, options: &PerformanceOptions<'_>) -> Result<(), JsValue> {
+    measure_with_js_options(name, &options.to_js())
+}
+
+/// State of a [WASMLayer] for reporting timings
+pub(crate) struct Timings {
+    /// `performance.mark` and `performance.measure` exist in this environment
+    pub(crate) available: bool,
+    #[cfg(feature = "console")]
+    warned_unavailable: AtomicBool,
+}
+
+impl Timings {
+    pub(crate) fn new() -> Self {
+        Timings {
+            available: detect_performance(),
+            #[cfg(feature = "console")]
+            warned_unavailable: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether marks and measures can be reported, warns once in the console if they can't
+    pub(crate) fn available(&self) -> bool {
+        #[cfg(feature = "console")]
+        if !self.available && !self.warned_unavailable.swap(true, Ordering::Relaxed) {
+            warn1(
+                "tracing-wasm: performance.mark or performance.measure is unavailable, timings will not be reported"
+                    .to_string(),
+            );
+        }
+        self.available
+    }
+}
+
+impl WASMLayer {
+    /// Mark when the layer is created and measure it from the navigation start, as an anchor for the other measures
+    pub(crate) fn mark_init(&self) {
+        let name = format!("{}wasm-layer-init", self.config.mark_prefix);
+        mark(&name);
+        let _ = measure_from_origin(name);
+    }
+
+    /// Mark and measure an event so you can see a little blip in the profile
+    pub(crate) fn report_event_timing(
+        &self,
+        meta: &tracing::Metadata<'_>,
+        recorder: &StringRecorder,
+    ) {
+        let level = meta.level();
+        let mark_name = format!(
+            "{}c{:x}",
+            self.config.mark_prefix,
+            self.shared.last_event_id.fetch_add(1, Ordering::Relaxed)
+        );
+        mark(&mark_name);
+        let name = if self.config.structured_measure_names {
+            structured_measure_name(
+                &[
+                    "event",
+                    level.as_str(),
+                    meta.target(),
+                    meta.module_path().unwrap_or("..."),
+                ],
+                &recorder.structured_fields(),
+            )
+        } else {
+            let target = if self.config.show_target {
+                format!(" {}", meta.target())
+            } else {
+                String::new()
+            };
+            format!(
+                "{}{}{} {}{} {}",
+                measure_tag(&self.config.message_prefix),
+                level,
+                target,
+                meta.module_path().unwrap_or("..."),
+                thread_display_suffix(),
+                recorder,
+            )
+        };
+        if self.config.measure_detail {
+            let options = PerformanceOptions {
+                start: Some(&mark_name),
+                end: None,
+                detail: Some((
+                    MeasureDetail::new(meta, recorder.message.as_deref()),
+                    recorder,
+                )),
+            };
+            let _ = measure_with_options(name, &options);
+        } else {
+            let _ = measure(name, mark_name);
+        }
+    }
+
+    /// Mark an event with its message, see [WASMLayerConfigBuilder::set_events_as_marks](super::WASMLayerConfigBuilder::set_events_as_marks)
+    pub(crate) fn mark_event(&self, meta: &tracing::Metadata<'_>, recorder: &StringRecorder) {
+        let name = match &recorder.message {
+            Some(message) if !message.is_empty() => {
+                format!("{}{}", measure_tag(&self.config.message_prefix), message)
+            }
+            _ => format!(
+                "{}{} {}",
+                measure_tag(&self.config.message_prefix),
+                meta.level(),
+                meta.target()
+            ),
+        };
+        if self.config.measure_detail {
+            let options = PerformanceOptions {
+                start: None,
+                end: None,
+                detail: Some((
+                    MeasureDetail::new(meta, recorder.message.as_deref()),
+                    recorder,
+                )),
+            };
+            mark_with_options(&name, &options);
+        } else {
+            mark(&name);
+        }
+    }
+
+    /// Whether a span is marked when entered and measured when exited, spans shown with `console.table` are not
+    pub(crate) fn measures_span<S: for<'a> LookupSpan<'a>>(
+        &self,
+        span_ref: &SpanRef<'_, S>,
+    ) -> bool {
+        #[cfg(feature = "console")]
+        let tabled = self.id.get::<TabledSpan>(&span_ref.extensions()).is_some();
+        #[cfg(not(feature = "console"))]
+        let tabled = false;
+        !tabled && self.timings_enabled(span_ref.metadata()) && self.timings.available()
+    }
+
+    /// Mark the start of a span, measured by [WASMLayer::measure_span] when it is exited
+    pub(crate) fn mark_span_enter(&self, id: &tracing::Id) {
+        mark(&mark_name(&self.config.mark_prefix, id));
+    }
+
+    /// Mark the end of a span and measure it from its start
+    pub(crate) fn measure_span<S: for<'a> LookupSpan<'a>>(
+        &self,
+        id: &tracing::Id,
+        span_ref: &SpanRef<'_, S>,
+    ) {
+        let meta = span_ref.metadata();
+        let start_name = mark_name(&self.config.mark_prefix, id);
+        let mut end_name = start_name.clone();
+        end_name.write_str("-end").unwrap();
+        mark(&end_name);
+        let extensions = span_ref.extensions();
+        let debug_record = self.id.get::<StringRecorder>(&extensions);
+        // with measure detail, the fields are passed in the detail rather than in the name
+        let fields_in_name = debug_record.filter(|_| !self.config.measure_detail);
+        let name = if self.config.flamegraph_compatible {
+            meta.name().to_string()
+        } else if self.config.structured_measure_names {
+            structured_measure_name(
+                &[
+                    "span",
+                    meta.name(),
+                    meta.module_path().unwrap_or("..."),
+                    meta.level().as_str(),
+                ],
+                &fields_in_name
+                    .map(StringRecorder::structured_fields)
+                    .unwrap_or_default(),
+            )
+        } else {
+            let template = self.config.measure_name_template;
+            let mut name = format!(
+                "{}\"{}\"{}",
+                measure_tag(&self.config.message_prefix),
+                meta.name(),
+                thread_display_suffix(),
+            );
+            if template.shows_module() {
+                write!(name, " {}", meta.module_path().unwrap_or("...")).unwrap();
+            }
+            match fields_in_name {
+                Some(debug_record) if template.shows_fields() => {
+                    write!(name, " {}", debug_record).unwrap();
+                }
+                _ => {}
+            }
+            name
+        };
+        if self.config.measure_detail {
+            let options = PerformanceOptions {
+                start: Some(&start_name),
+                end: Some(&end_name),
+                detail: debug_record.map(|debug_record| {
+                    (MeasureDetail::new(meta, Some(meta.name())), debug_record)
+                }),
+            };
+            let _ = measure_with_options(name, &options);
+        } else {
+            let _ = measure2(name, &start_name, &end_name);
+        }
+    }
+}
+
+/// The `detail` of marks and measures, see [WASMLayerConfigBuilder::set_measure_detail](super::WASMLayerConfigBuilder::set_measure_detail)
+/// for its shape in JS
+#[cfg_attr(not(any(test, target_arch = "wasm32")), allow(dead_code))]
+pub(crate) struct MeasureDetail<'a> {
+    pub(crate) level: &'static str,
+    pub(crate) target: &'a str,
+    pub(crate) file: Option<&'a str>,
+    pub(crate) line: Option<u32>,
+    /// The message of an event or the name of a span
+    pub(crate) message: Option<&'a str>,
+}
+
+impl<'a> MeasureDetail<'a> {
+    pub(crate) fn new<'m: 'a>(meta: &tracing::Metadata<'m>, message: Option<&'a str>) -> Self {
+        MeasureDetail {
+            level: meta.level().as_str(),
+            target: meta.target(),
+            file: meta.file(),
+            line: meta.line(),
+            message,
+        }
+    }
+
+    /// Convert to a JS object, with the fields of `recorder`
+    #[cfg(target_arch = "wasm32")]
+    fn to_js(&self, recorder: &StringRecorder) -> JsValue {
+        let detail = js_sys::Object::new();
+        let set = |name: &str, value: JsValue| {
+            let _ = js_sys::Reflect::set(&detail, &JsValue::from_str(name), &value);
+        };
+        set("level", self.level.into());
+        set("target", self.target.into());
+        set("file", self.file.map_or(JsValue::NULL, JsValue::from));
+        set("line", self.line.map_or(JsValue::NULL, JsValue::from));
+        set("message", self.message.map_or(JsValue::NULL, JsValue::from));
+        set("fields", recorder.fields_object().into());
+        detail.into()
+    }
+}
+
+/// The options of `performance.mark` and `performance.measure` with measure detail, which are converted
+/// to a JS object when they are reported
+#[cfg_attr(not(any(test, target_arch = "wasm32")), allow(dead_code))]
+pub(crate) struct PerformanceOptions<'a> {
+    pub(crate) start: Option<&'a str>,
+    pub(crate) end: Option<&'a str>,
+    /// The detail, with the recorder of its fields
+    pub(crate) detail: Option<(MeasureDetail<'a>, &'a StringRecorder)>,
+}
+
+impl<'a> PerformanceOptions<'a> {
+    #[cfg(target_arch = "wasm32")]
+    fn to_js(&self) -> JsValue {
+        let options = js_sys::Object::new();
+        let set = |name: &str, value: JsValue| {
+            let _ = js_sys::Reflect::set(&options, &JsValue::from_str(name), &value);
+        };
+        if let Some(start) = self.start {
+            set("start", start.into());
+        }
+        if let Some(end) = self.end {
+            set("end", end.into());
+        }
+        if let Some((detail, recorder)) = &self.detail {
+            set("detail", detail.to_js(recorder));
+        }
+        options.into()
+    }
+}
+
+impl MeasureNameTemplate {
+    fn shows_module(self) -> bool {
+        matches!(
+            self,
+            MeasureNameTemplate::Full | MeasureNameTemplate::NameAndModule
+        )
+    }
+
+    fn shows_fields(self) -> bool {
+        matches!(
+            self,
+            MeasureNameTemplate::Full | MeasureNameTemplate::NameAndFields
+        )
+    }
+}
+
+/// The tag of [WASMLayerConfigBuilder::set_message_prefix](super::WASMLayerConfigBuilder::set_message_prefix)
+/// at the start of a measure name
+fn measure_tag(message_prefix: &str) -> String {
+    if message_prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{} ", message_prefix)
+    }
+}
+
+/// Check that the global `performance` object has `mark` and `measure` functions
+#[cfg(target_arch = "wasm32")]
+fn detect_performance() -> bool {
+    let performance =
+        match js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("performance")) {
+            Ok(performance) if performance.is_object() => performance,
+            _ => return false,
+        };
+    ["mark", "measure"].iter().all(|name| {
+        js_sys::Reflect::get(&performance, &JsValue::from_str(name))
+            .map(|function| function.is_function())
+            .unwrap_or(false)
+    })
+}
+
+/// There is no JS environment outside of wasm
+#[cfg(not(target_arch = "wasm32"))]
+fn detect_performance() -> bool {
+    false
+}
+
+/// Name of a span mark, written on the stack unless the mark prefix is long,
+/// as spans can be entered and exited in hot loops
+#[derive(Clone)]
+pub(crate) enum MarkName {
+    Inline { buf: [u8; 64], len: usize },
+    Heap(String),
+}
+
+impl Write for MarkName {
+    fn write_str(&mut self, part: &str) -> core::fmt::Result {
+        match self {
+            MarkName::Inline { buf, len } if *len + part.len() <= buf.len() => {
+                buf[*len..*len + part.len()].copy_from_slice(part.as_bytes());
+                *len += part.len();
+            }
+            MarkName::Inline { .. } => {
+                let mut name = String::with_capacity(self.len() + part.len());
+                name.push_str(self);
+                name.push_str(part);
+                *self = MarkName::Heap(name);
+            }
+            MarkName::Heap(name) => name.push_str(part),
+        }
+        Ok(())
+    }
+}
+
+impl core::ops::Deref for MarkName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            // only whole `&str`s are copied into the buffer
+            MarkName::Inline { buf, len } => core::str::from_utf8(&buf[..*len]).unwrap(),
+            MarkName::Heap(name) => name,
+        }
+    }
+}
+
+#[cfg(not(feature = "mark-with-rayon-thread-index"))]
+pub(crate) fn mark_name(prefix: &str, id: &tracing::Id) -> MarkName {
+    let mut name = MarkName::Inline {
+        buf: [0; 64],
+        len: 0,
+    };
+    write!(name, "{}t{:x}", prefix, id.into_u64()).unwrap();
+    name
+}
+#[cfg(feature = "mark-with-rayon-thread-index")]
+pub(crate) fn mark_name(prefix: &str, id: &tracing::Id) -> MarkName {
+    let mut name = MarkName::Inline {
+        buf: [0; 64],
+        len: 0,
+    };
+    write!(
+        name,
+        "{}t{:x}-{}",
+        prefix,
+        id.into_u64(),
+        rayon::current_thread_index().unwrap_or(999)
+    )
+    .unwrap();
+    name
+}
+
+impl StringRecorder {
+    /// The message and fields as `;` separated `name=value` pairs, see [WASMLayerConfigBuilder::set_structured_measure_names](super::WASMLayerConfigBuilder::set_structured_measure_names)
+    pub(crate) fn structured_fields(&self) -> String {
+        let mut fields = String::new();
+        let message = self.message.as_ref().map(|message| ("message", message));
+        let others = self.fields.iter().map(|(name, value)| (*name, value));
+        for (idx, (name, value)) in message.into_iter().chain(others).enumerate() {
+            if idx > 0 {
+                fields.push(';');
+            }
+            write!(fields, "{}={}", name, escape_measure_part(value)).unwrap();
+        }
+        fields
+    }
+}
+
+/// Join the parts of a structured measure name, followed by its already escaped fields,
+/// see [WASMLayerConfigBuilder::set_structured_measure_names](super::WASMLayerConfigBuilder::set_structured_measure_names)
+pub(crate) fn structured_measure_name(parts: &[&str], fields: &str) -> String {
+    let mut name = String::new();
+    for part in parts {
+        name.push_str(&escape_measure_part(part));
+        name.push('|');
+    }
+    name.push_str(fields);
+    name
+}
+
+/// Escape the separators of structured measure names with a `\`
+fn escape_measure_part(part: &str) -> String {
+    let mut escaped = String::with_capacity(part.len());
+    for c in part.chars() {
+        if c == '\\' || c == '|' || c == ';' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,100 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tracing::Subscriber;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::Registry;
+
+use tracing_wasm::{wasm_layer, ConsoleConfig, SharedWASMLayer, WASMLayer, WASMLayerConfigBuilder};
+
+struct CountingLayer {
+    events: Arc<AtomicUsize>,
+    spans: Arc<AtomicUsize>,
+}
+
+impl<S: Subscriber> Layer<S> for CountingLayer {
+    fn on_new_span(
+        &self,
+        _attrs: &tracing::span::Attributes<'_>,
+        _id: &tracing::Id,
+        _ctx: Context<'_, S>,
+    ) {
+        self.spans.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_event(&self, _event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        self.events.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_compose_with_level_filter() {
+    // nothing may reach the JS bindings when running on the host
+    let config = WASMLayerConfigBuilder::new()
+        .set_console_config(ConsoleConfig::NoReporting)
+        .set_report_events_in_timings(false)
+        .set_report_spans_in_timings(false)
+        .build();
+
+    let events = Arc::new(AtomicUsize::new(0));
+    let spans = Arc::new(AtomicUsize::new(0));
+    let subscriber = Registry::default()
+        .with(wasm_layer(config))
+        .with(LevelFilter::INFO)
+        .with(CountingLayer {
+            events: events.clone(),
+            spans: spans.clone(),
+        });
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("outer", answer = 42);
+        let _enter = span.enter();
+        tracing::debug_span!("filtered").in_scope(|| {
+            tracing::debug!("filtered out");
+        });
+        tracing::info!(field = "value", "kept");
+        tracing::warn!("kept");
+    });
+
+    assert_eq!(events.load(Ordering::Relaxed), 2);
+    assert_eq!(spans.load(Ordering::Relaxed), 1);
+}
+
+fn span_data_config() -> tracing_wasm::WASMLayerConfig {
+    WASMLayerConfigBuilder::new()
+        .set_console_config(ConsoleConfig::NoReporting)
+        .set_report_events_in_timings(false)
+        .set_report_spans_in_timings(false)
+        .set_show_span_fields(true)
+        .set_show_span_relative_time(true)
+        .set_log_span_busy_time(true)
+        .build()
+}
+
+fn record_spans() {
+    let span = tracing::info_span!("outer", answer = 42, user = tracing::field::Empty);
+    span.record("user", "alice");
+    span.in_scope(|| {
+        tracing::info_span!("inner").in_scope(|| tracing::info!("kept"));
+    });
+}
+
+#[test]
+fn test_compose_two_wasm_layers() {
+    let subscriber = Registry::default()
+        .with(wasm_layer(span_data_config()))
+        .with(wasm_layer(span_data_config()));
+
+    tracing::subscriber::with_default(subscriber, record_spans);
+}
+
+#[test]
+fn test_compose_shared_and_plain_wasm_layers() {
+    let shared = SharedWASMLayer::new(WASMLayer::new(span_data_config()));
+    let subscriber = Registry::default()
+        .with(shared.clone())
+        .with(WASMLayer::new(span_data_config()));
+
+    tracing::subscriber::with_default(subscriber, record_spans);
+}
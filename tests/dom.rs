@@ -0,0 +1,37 @@
+//! Run with `wasm-pack test --chrome --headless --features dom-sink` (or `--firefox`), the DOM is only available
+//! in a browser
+#![cfg(all(target_arch = "wasm32", feature = "dom-sink"))]
+
+use tracing_subscriber::layer::SubscriberExt;
+use wasm_bindgen_test::*;
+
+use tracing_wasm::{WASMLayer, WASMLayerConfigBuilder};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+fn test_dom_sink_element() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    // not attached to the document, so it can't be found by an id
+    let element = document.create_element("div").unwrap();
+    let config = WASMLayerConfigBuilder::new()
+        .set_report_events_in_timings(false)
+        .set_show_origin(false)
+        .set_dom_sink_element(element.clone(), 2)
+        .build();
+    let subscriber = tracing_subscriber::Registry::default().with(WASMLayer::new(config));
+
+    tracing::subscriber::with_default(subscriber, || {
+        for idx in 0..3 {
+            tracing::info!(idx, "appended");
+        }
+    });
+
+    assert_eq!(element.child_element_count(), 2);
+    let oldest = element
+        .first_element_child()
+        .unwrap()
+        .text_content()
+        .unwrap();
+    assert!(oldest.contains("idx = 1;"));
+}
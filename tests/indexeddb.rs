@@ -0,0 +1,62 @@
+//! Run with `wasm-pack test --chrome --headless --features indexeddb-sink` (or `--firefox`), IndexedDB is
+//! only available in a browser
+#![cfg(all(target_arch = "wasm32", feature = "indexeddb-sink"))]
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::*;
+
+use tracing_wasm::{ConsoleConfig, WASMLayerConfigBuilder};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+/// Let the pending entries be written and the oldest deleted
+async fn sleep(ms: i32) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        let set_timeout = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))
+            .unwrap()
+            .unchecked_into::<js_sys::Function>();
+        set_timeout
+            .call2(&JsValue::NULL, &resolve, &JsValue::from(ms))
+            .unwrap();
+    });
+    JsFuture::from(promise).await.unwrap();
+}
+
+#[wasm_bindgen_test]
+async fn test_store_cap_and_export() {
+    // a database of its own, in case the browser profile is reused
+    let database_name = format!("tracing-wasm-test-{}", js_sys::Date::now());
+    tracing_wasm::set_as_global_default_with_config(
+        WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::NoReporting)
+            .set_report_events_in_timings(false)
+            .set_indexeddb_sink(&database_name, 3)
+            .build(),
+    );
+
+    // pending while the database opens, only the last 3 are kept
+    for idx in 0..5 {
+        tracing::info!(idx, "stored");
+    }
+    sleep(200).await;
+    // written in the store, which is trimmed to the last 3
+    for idx in 5..7 {
+        tracing::info!(idx, "stored");
+    }
+    sleep(200).await;
+
+    let logs = JsFuture::from(tracing_wasm::export_indexeddb_logs(&database_name))
+        .await
+        .unwrap()
+        .as_string()
+        .unwrap();
+    let logs: Vec<serde_json::Value> = serde_json::from_str(&logs).unwrap();
+    let indices: Vec<&str> = logs
+        .iter()
+        .map(|entry| entry["fields"]["idx"].as_str().unwrap())
+        .collect();
+    assert_eq!(indices, vec!["4", "5", "6"]);
+    assert_eq!(logs[0]["message"], "stored");
+    assert!(logs[0]["time"].as_str().unwrap().ends_with('Z'));
+}
@@ -0,0 +1,15 @@
+//! Setting the global default, in its own test binary as it can only be done once per process
+
+#[test]
+fn test_init_guarded_by_is_installed() {
+    assert!(!tracing_wasm::is_installed());
+
+    for _ in 0..2 {
+        if !tracing_wasm::is_installed() {
+            tracing_wasm::init();
+        }
+    }
+
+    assert!(tracing_wasm::is_installed());
+    tracing::info!("logged once the layer is installed");
+}
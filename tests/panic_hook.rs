@@ -0,0 +1,47 @@
+//! Panics reported by the panic hook, in their own test binary as the panic hook and the global default
+//! are set once per process
+
+use std::sync::{Arc, Mutex};
+
+use tracing_wasm::{ConsoleConfig, WASMLayerConfigBuilder};
+
+#[test]
+fn test_panic_logged_at_error() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    tracing_wasm::set_as_global_default_with_config(
+        WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::NoReporting)
+            .set_report_events_in_timings(false)
+            .set_on_event_hook(move |event| {
+                recorded.lock().unwrap().push((
+                    event.level,
+                    event.target.to_string(),
+                    event.message.map(str::to_string),
+                    event.fields.to_vec(),
+                ));
+            })
+            .build(),
+    );
+    tracing_wasm::install_panic_hook();
+    // the hook is only set once
+    tracing_wasm::install_panic_hook();
+
+    let line = line!() + 2;
+    let result = std::panic::catch_unwind(|| {
+        panic!("boom {}", 42);
+    });
+
+    assert!(result.is_err());
+    // copied, as a failed assertion logs through the event hook too
+    let events = events.lock().unwrap().clone();
+    assert_eq!(
+        events,
+        vec![(
+            tracing::Level::ERROR,
+            "panic".to_string(),
+            Some("boom 42".to_string()),
+            vec![("location", format!("tests/panic_hook.rs:{}:9", line))],
+        )]
+    );
+}
@@ -0,0 +1,31 @@
+//! Events emitted while the global layer reports an event, in their own test binary as the global
+//! default can only be set once per process
+//!
+//! Unlike scoped defaults, the global default is dispatched to without any reentrancy guard from `tracing`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tracing_wasm::{ConsoleConfig, WASMLayerConfigBuilder};
+
+#[test]
+fn test_event_emitted_while_reporting_an_event() {
+    let hook_calls = Arc::new(AtomicUsize::new(0));
+    let counter = hook_calls.clone();
+    tracing_wasm::set_as_global_default_with_config(
+        WASMLayerConfigBuilder::new()
+            .set_console_config(ConsoleConfig::NoReporting)
+            .set_report_events_in_timings(false)
+            .set_on_event_hook(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                // would report itself endlessly without the reentrancy guard
+                tracing::info!("emitted by the hook");
+            })
+            .build(),
+    );
+
+    tracing::info!("first");
+    tracing::info!("second");
+
+    assert_eq!(hook_calls.load(Ordering::Relaxed), 2);
+}
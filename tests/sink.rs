@@ -0,0 +1,54 @@
+//! Run with `wasm-pack test --node` (or in a browser), the sink is a JS function
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::*;
+
+use tracing_wasm::ConsoleSink;
+
+#[wasm_bindgen_test]
+fn test_sink_registered_once() {
+    let function = js_sys::Function::new_with_args("message, level", "");
+    let sink = ConsoleSink::new(function.clone());
+
+    assert_eq!(ConsoleSink::new(function), sink);
+    assert_ne!(
+        ConsoleSink::new(js_sys::Function::new_with_args("message, level", "")),
+        sink
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_sink_registered_again_after_drop() {
+    let function = js_sys::Function::new_with_args("message, level", "");
+    let sink = ConsoleSink::new(function.clone());
+    let clone = sink.clone();
+    let unregistered = format!("{:?}", sink);
+    drop(sink);
+
+    // a clone keeps the registration
+    assert_eq!(ConsoleSink::new(function.clone()), clone);
+    drop(clone);
+
+    assert_ne!(format!("{:?}", ConsoleSink::new(function)), unregistered);
+}
+
+#[wasm_bindgen_test]
+fn test_sink_called_after_builder_dropped() {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let function = js_sys::Function::new_with_args(
+        "message, level",
+        "globalThis.sinkCalls = (globalThis.sinkCalls || 0) + 1",
+    );
+    let config = tracing_wasm::WASMLayerConfigBuilder::new()
+        .set_sink(function)
+        .set_report_events_in_timings(false)
+        .build();
+    let subscriber =
+        tracing_subscriber::Registry::default().with(tracing_wasm::WASMLayer::new(config));
+
+    tracing::subscriber::with_default(subscriber, || tracing::info!("to the sink"));
+
+    let calls = js_sys::Reflect::get(&js_sys::global(), &"sinkCalls".into()).unwrap();
+    assert_eq!(calls.as_f64(), Some(1.0));
+}
@@ -0,0 +1,20 @@
+//! Run with `wasm-pack test --chrome --headless` (or `--firefox`), the tests run inside a dedicated worker
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+#[wasm_bindgen_test]
+fn test_layer_in_dedicated_worker() {
+    // there is no `window` in a worker, only its own global scope
+    let window = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("window")).unwrap();
+    assert!(window.is_undefined());
+
+    tracing_wasm::try_set_as_global_default().expect("default global");
+
+    let span = tracing::info_span!("worker span", answer = 42);
+    let _enter = span.enter();
+    tracing::info!(field = "value", "event inside a worker");
+}